@@ -1,4 +1,8 @@
-use crate::patch::{PatchBatch, PatchOp};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::patch::PatchOp;
+use crate::NodeId;
 
 #[derive(Debug, Default, Clone)]
 pub struct EffectQueue {
@@ -14,11 +18,174 @@ impl EffectQueue {
         self.pending.push(op);
     }
 
-    pub fn commit(&mut self) -> PatchBatch {
-        self.pending.drain(..).collect()
+    pub fn commit(&mut self) -> Vec<PatchOp> {
+        coalesce(self.pending.drain(..).collect())
     }
 
     pub fn pending(&self) -> &[PatchOp] {
         &self.pending
     }
 }
+
+/// The slot an overwriting op writes to; a later op with the same key makes an
+/// earlier one dead.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum WriteKey<'a> {
+    Text(NodeId),
+    Attr(NodeId, Cow<'a, str>),
+}
+
+impl<'a> WriteKey<'a> {
+    pub(crate) fn of(op: &'a PatchOp) -> Option<Self> {
+        match op {
+            PatchOp::SetText { node, .. } => Some(WriteKey::Text(*node)),
+            PatchOp::SetAttr { node, name, .. } | PatchOp::RemoveAttr { node, name } => {
+                Some(WriteKey::Attr(*node, Cow::Borrowed(name)))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn node(&self) -> NodeId {
+        match self {
+            WriteKey::Text(node) | WriteKey::Attr(node, _) => *node,
+        }
+    }
+
+    pub(crate) fn into_owned(self) -> WriteKey<'static> {
+        match self {
+            WriteKey::Text(node) => WriteKey::Text(node),
+            WriteKey::Attr(node, name) => WriteKey::Attr(node, Cow::Owned(name.into_owned())),
+        }
+    }
+}
+
+/// Collapses a tick's ops into a smaller batch with the same final effect on
+/// the host.
+///
+/// - only the last `SetText` per node and `SetAttr`/`RemoveAttr` per
+///   (node, name) survive;
+/// - writes to a node that is removed later in the batch are dropped, and so
+///   are writes to nodes the batch attached beneath it, which the host
+///   destroys along with it;
+/// - `EnsureNode`, `Insert` and `InsertBefore` ops that only create or move a
+///   node right before its `Remove`, with nothing but writes in between, are
+///   dropped.
+///
+/// Every `Remove` is kept: `EnsureNode` is create-or-noop, so the node may
+/// have existed before the batch, and removing a missing node is a no-op.
+/// Other structural ops are kept too, in their original order, since whether
+/// they matter depends on the host tree before the batch.
+pub(crate) fn coalesce(ops: Vec<PatchOp>) -> Vec<PatchOp> {
+    let mut keep = vec![true; ops.len()];
+    let mut tree = Attachments::default();
+    let mut writes: BTreeMap<NodeId, Vec<usize>> = BTreeMap::new();
+    // Structural ops on one node since the last structural op on another.
+    let mut run: Option<(NodeId, Vec<usize>)> = None;
+    for (index, op) in ops.iter().enumerate() {
+        if let Some(key) = WriteKey::of(op) {
+            writes.entry(key.node()).or_default().push(index);
+            continue;
+        }
+        match op {
+            PatchOp::Remove { node } => {
+                if let Some((_, indices)) = run.take().filter(|(run_node, _)| run_node == node) {
+                    for index in indices {
+                        keep[index] = false;
+                    }
+                }
+                for gone in tree.remove(*node) {
+                    for index in writes.remove(&gone).unwrap_or_default() {
+                        keep[index] = false;
+                    }
+                }
+                continue;
+            }
+            PatchOp::EnsureNode { node, .. } => tree.detach(*node),
+            PatchOp::Insert { parent, child } | PatchOp::InsertBefore { parent, child, .. } => {
+                tree.attach(*parent, *child)
+            }
+            _ => {}
+        }
+        let node = match op {
+            PatchOp::EnsureNode { node, .. } => Some(*node),
+            PatchOp::Insert { parent, child } if parent != child => Some(*child),
+            PatchOp::InsertBefore {
+                parent,
+                child,
+                before,
+            } if parent != child && before != child => Some(*child),
+            _ => None,
+        };
+        run = match (run.take(), node) {
+            (Some((run_node, mut indices)), Some(node)) if run_node == node => {
+                indices.push(index);
+                Some((node, indices))
+            }
+            (_, Some(node)) => Some((node, vec![index])),
+            (_, None) => None,
+        };
+    }
+
+    let mut seen = BTreeSet::new();
+    for (index, op) in ops.iter().enumerate().rev() {
+        if !keep[index] {
+            continue;
+        }
+        if let Some(key) = WriteKey::of(op) {
+            if !seen.insert(key) {
+                keep[index] = false;
+            }
+        }
+    }
+
+    ops.into_iter()
+        .zip(keep)
+        .filter_map(|(op, keep)| keep.then_some(op))
+        .collect()
+}
+
+/// Parent links the batch created, for nodes it has not removed or ensured
+/// since.
+///
+/// A node linked here is, whatever the host tree looked like before the batch,
+/// either a child of its recorded parent or already destroyed, so removing
+/// the parent is known to destroy it.
+#[derive(Debug, Default)]
+struct Attachments {
+    parents: BTreeMap<NodeId, NodeId>,
+    children: BTreeMap<NodeId, BTreeSet<NodeId>>,
+}
+
+impl Attachments {
+    fn attach(&mut self, parent: NodeId, child: NodeId) {
+        self.detach(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().insert(child);
+    }
+
+    /// Forgets where `node` is; an `EnsureNode` may have created it afresh,
+    /// unattached.
+    fn detach(&mut self, node: NodeId) {
+        if let Some(parent) = self.parents.remove(&node) {
+            if let Some(siblings) = self.children.get_mut(&parent) {
+                siblings.remove(&node);
+            }
+        }
+    }
+
+    /// Forgets `node` and every node linked beneath it, returning them.
+    fn remove(&mut self, node: NodeId) -> Vec<NodeId> {
+        self.detach(node);
+        let mut gone = vec![node];
+        let mut next = 0;
+        while let Some(&parent) = gone.get(next) {
+            next += 1;
+            for child in self.children.remove(&parent).unwrap_or_default() {
+                self.parents.remove(&child);
+                gone.push(child);
+            }
+        }
+        gone
+    }
+}
@@ -1,20 +1,237 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use crate::graph::Reads;
 use crate::patch::{PatchBatch, PatchOp};
-use crate::telemetry::{TelemetryRecorder, TickResult};
-use crate::{NodeId, Scheduler, SchedulerError, Store};
+use crate::path::Path;
+use crate::selector::{
+    Binding, BoxedSelector, PendingToken, Resolver, SelectorContext, SelectorError, SelectorOutput,
+};
+use crate::store::StoreSnapshot;
+use crate::telemetry::{GuardrailEvent, TelemetryRecorder, TickResult};
+use crate::transaction::Transaction;
+use crate::{DependencyGraph, GraphError, Lane, NodeId, Scheduler, SchedulerError, Store, Value};
+
+/// Limits how much work a single commit may spend.
+///
+/// Selector recomputation stops once either limit is reached and the remaining
+/// dirty selectors resume on the next tick. Ops written directly to the input
+/// lane always flush.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickBudget {
+    max_ops: usize,
+    max_duration: Option<Duration>,
+}
+
+impl TickBudget {
+    pub fn unlimited() -> Self {
+        Self {
+            max_ops: usize::MAX,
+            max_duration: None,
+        }
+    }
+
+    pub fn ops(max_ops: usize) -> Self {
+        Self::unlimited().with_max_ops(max_ops)
+    }
+
+    pub fn duration(max_duration: Duration) -> Self {
+        Self::unlimited().with_max_duration(max_duration)
+    }
+
+    pub fn with_max_ops(mut self, max_ops: usize) -> Self {
+        self.max_ops = max_ops;
+        self
+    }
+
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn max_ops(&self) -> usize {
+        self.max_ops
+    }
+
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration
+    }
+}
+
+impl Default for TickBudget {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[derive(Debug)]
+struct RegisteredSelector {
+    selector: BoxedSelector,
+    lane: Lane,
+    binding: Binding,
+}
+
+/// How dirty a node was before its first write in the current tick.
+#[derive(Debug)]
+struct UndoEntry {
+    was_dirty: bool,
+    /// The changed paths the node was dirty with, if only paths were.
+    dirty_paths: Option<BTreeSet<Path>>,
+}
+
+/// Selector outputs computed during one commit.
+///
+/// Doubles as the resolver for selector reads, so a selector that reads
+/// another pending selector gets its fresh output, computed on demand if the
+/// planned order has not reached it yet.
+struct Recompute<'e> {
+    selectors: &'e BTreeMap<NodeId, RegisteredSelector>,
+    recorder: Option<&'e mut TelemetryRecorder>,
+    pending: BTreeSet<NodeId>,
+    /// Selectors being evaluated, outermost first.
+    in_progress: Vec<NodeId>,
+    outputs: BTreeMap<NodeId, Value>,
+    published: BTreeSet<NodeId>,
+    failure: Option<RecomputeError>,
+    resolutions: &'e BTreeMap<NodeId, Value>,
+    went_pending: BTreeMap<NodeId, PendingToken>,
+    next_request: &'e mut u64,
+    /// The dependencies each evaluated selector had before this commit.
+    previous_reads: BTreeMap<NodeId, Reads>,
+}
+
+/// Why recomputing selectors stopped a commit.
+enum RecomputeError {
+    Scheduler(SchedulerError),
+    Selector(NodeId, SelectorError),
+    Graph(GraphError),
+}
+
+impl From<SchedulerError> for RecomputeError {
+    fn from(err: SchedulerError) -> Self {
+        Self::Scheduler(err)
+    }
+}
+
+impl Recompute<'_> {
+    /// Returns the output of selector `id` for this tick, evaluating it unless
+    /// that already happened on demand.
+    ///
+    /// A failed evaluation yields `None` and is kept in `failure`, so a failure
+    /// while computing a selector on demand reaches the commit loop too. So
+    /// does a cycle formed by on-demand reads, which the planned order cannot
+    /// see because the edges closing it are only recorded by this tick.
+    fn output(&mut self, store: &Store, graph: &mut DependencyGraph, id: NodeId) -> Option<Value> {
+        if let Some(output) = self.outputs.get(&id) {
+            return Some(output.clone());
+        }
+        let selectors = self.selectors;
+        let entry = selectors.get(&id)?;
+        if let Some(start) = self.in_progress.iter().position(|&node| node == id) {
+            let mut path = self.in_progress[start..].to_vec();
+            path.push(id);
+            self.failure
+                .get_or_insert(RecomputeError::Graph(GraphError::Cycle(path)));
+            return None;
+        }
+        self.in_progress.push(id);
+        self.previous_reads
+            .entry(id)
+            .or_insert_with(|| graph.dependency_reads(id));
+        let mut recorder = self.recorder.take();
+        let result =
+            entry
+                .selector
+                .try_evaluate_with_resolver(store, graph, recorder.as_deref_mut(), self);
+        self.recorder = recorder;
+        self.in_progress.pop();
+        match result {
+            Ok(output) => {
+                self.outputs.insert(id, output.clone());
+                Some(output)
+            }
+            Err(err) => {
+                self.failure
+                    .get_or_insert(RecomputeError::Selector(id, err));
+                None
+            }
+        }
+    }
+}
+
+impl Resolver for Recompute<'_> {
+    fn resolve(
+        &mut self,
+        store: &Store,
+        graph: &mut DependencyGraph,
+        node: NodeId,
+    ) -> Option<Value> {
+        if self.outputs.contains_key(&node) || self.pending.contains(&node) {
+            self.output(store, graph, node)
+        } else {
+            None
+        }
+    }
+
+    fn resolution(&self, node: NodeId) -> Option<Value> {
+        self.resolutions.get(&node).cloned()
+    }
+
+    fn mark_pending(&mut self, node: NodeId) -> PendingToken {
+        let token = PendingToken::new(node, *self.next_request);
+        *self.next_request += 1;
+        self.went_pending.insert(node, token);
+        token
+    }
+}
 
 #[derive(Debug)]
 pub struct Engine {
     store: Store,
+    graph: DependencyGraph,
+    selectors: BTreeMap<NodeId, RegisteredSelector>,
+    /// Selectors taken out of recomputation after failing, until something
+    /// upstream of them is written.
+    quarantined: BTreeMap<NodeId, RegisteredSelector>,
+    /// Selectors whose binding has not been sent an output since they were
+    /// registered, so their next output is queued even if unchanged.
+    unpublished: BTreeSet<NodeId>,
+    dirty: BTreeSet<NodeId>,
+    /// Changed paths of dirty nodes written through `update`; a dirty node
+    /// without an entry changed as a whole.
+    dirty_paths: BTreeMap<NodeId, BTreeSet<Path>>,
+    write_log: BTreeMap<NodeId, UndoEntry>,
+    /// The store as it was at `begin_tick`, restored when the tick is
+    /// abandoned.
+    checkpoint: Option<StoreSnapshot>,
+    awaiting: BTreeMap<NodeId, PendingToken>,
+    resolutions: BTreeMap<NodeId, Value>,
+    next_request: u64,
     scheduler: Scheduler,
     telemetry: TelemetryRecorder,
+    tick_id: u64,
+    next_batch_id: u64,
 }
 
 impl Default for Engine {
     fn default() -> Self {
         Self {
             store: Store::new(),
+            graph: DependencyGraph::new(),
+            selectors: BTreeMap::new(),
+            quarantined: BTreeMap::new(),
+            unpublished: BTreeSet::new(),
+            dirty: BTreeSet::new(),
+            dirty_paths: BTreeMap::new(),
+            write_log: BTreeMap::new(),
+            checkpoint: None,
+            awaiting: BTreeMap::new(),
+            resolutions: BTreeMap::new(),
+            next_request: 0,
             scheduler: Scheduler::new(),
             telemetry: TelemetryRecorder::new(),
+            tick_id: 0,
+            next_batch_id: 1,
         }
     }
 }
@@ -24,30 +241,374 @@ impl Engine {
         Self::default()
     }
 
+    /// Registers a selector whose output drives the text of node `id`.
+    ///
+    /// The selector is evaluated on the next commit and afterwards whenever a
+    /// node it read is written.
+    ///
+    /// The closure may return a `Result` to signal failure; a failing or
+    /// panicking selector turns the commit into a fallback.
+    pub fn register_selector<F, R>(&mut self, id: NodeId, compute: F)
+    where
+        F: Fn(&mut SelectorContext<'_>) -> R + 'static,
+        R: SelectorOutput,
+    {
+        self.register_selector_in(Lane::Input, id, compute);
+    }
+
+    /// Registers a selector whose recomputed output is scheduled in `lane`.
+    pub fn register_selector_in<F, R>(&mut self, lane: Lane, id: NodeId, compute: F)
+    where
+        F: Fn(&mut SelectorContext<'_>) -> R + 'static,
+        R: SelectorOutput,
+    {
+        let selector = BoxedSelector::boxed(id, compute);
+        self.register_bound_selector_in(lane, selector, Binding::text(id));
+    }
+
+    /// Registers an already boxed selector whose output is written to
+    /// `binding` instead of the text of its own node.
+    ///
+    /// The output is still stored under the selector's id, so other selectors
+    /// can read it. Registering an id again replaces the selector.
+    pub fn register_bound_selector(&mut self, selector: BoxedSelector, binding: Binding) {
+        self.register_bound_selector_in(Lane::Input, selector, binding);
+    }
+
+    pub fn register_bound_selector_in(
+        &mut self,
+        lane: Lane,
+        selector: BoxedSelector,
+        binding: Binding,
+    ) {
+        let id = selector.id();
+        self.quarantined.remove(&id);
+        self.selectors.insert(
+            id,
+            RegisteredSelector {
+                selector,
+                lane,
+                binding,
+            },
+        );
+        self.graph.add_node(id);
+        self.dirty.insert(id);
+        self.unpublished.insert(id);
+    }
+
+    pub fn binding_of(&self, id: NodeId) -> Option<&Binding> {
+        self.selectors
+            .get(&id)
+            .or_else(|| self.quarantined.get(&id))
+            .map(|entry| &entry.binding)
+    }
+
+    /// Drops a selector, its stored output and every dependency edge it owned.
+    pub fn unregister_selector(&mut self, id: NodeId) -> bool {
+        self.dirty.remove(&id);
+        self.awaiting.remove(&id);
+        self.resolutions.remove(&id);
+        self.graph.remove_node(id);
+        self.unpublished.remove(&id);
+        let quarantined = self.quarantined.remove(&id).is_some();
+        let registered = self.selectors.remove(&id).is_some() || quarantined;
+        if registered {
+            self.store.remove_value(id);
+        }
+        registered
+    }
+
+    /// Whether selector `id` failed and is skipped until a node upstream of
+    /// it is written or it is registered again.
+    pub fn is_quarantined(&self, id: NodeId) -> bool {
+        self.quarantined.contains_key(&id)
+    }
+
+    /// Supplies the result of the asynchronous work behind `token`, i.e. the
+    /// value `SelectorContext::resolved` returns on the selector's next run.
+    ///
+    /// The selector is marked dirty, so the real output is emitted by the next
+    /// commit rather than by a commit of its own. Returns `false` if the
+    /// selector is not waiting on that request, e.g. because it re-ran and
+    /// went pending again since.
+    pub fn resolve_selector<V: Into<Value>>(&mut self, token: PendingToken, value: V) -> bool {
+        let id = token.selector();
+        if self.awaiting.get(&id) != Some(&token) {
+            return false;
+        }
+        self.resolutions.insert(id, value.into());
+        self.dirty.insert(id);
+        true
+    }
+
+    /// Whether selector `id` last committed a placeholder and awaits
+    /// `resolve_selector`.
+    pub fn is_awaiting(&self, id: NodeId) -> bool {
+        self.awaiting.contains_key(&id)
+    }
+
+    /// The token of the request selector `id` awaits, if any.
+    pub fn pending_token(&self, id: NodeId) -> Option<PendingToken> {
+        self.awaiting.get(&id).copied()
+    }
+
+    pub fn awaiting_selectors(&self) -> Vec<NodeId> {
+        self.awaiting.keys().copied().collect()
+    }
+
     pub fn begin_tick(&mut self) -> Result<(), SchedulerError> {
         self.scheduler.begin_tick()?;
         self.telemetry.begin_tick();
+        self.write_log.clear();
+        self.checkpoint = Some(self.store.snapshot());
+        self.tick_id += 1;
         Ok(())
     }
 
-    pub fn set_value<S: Into<String>>(
+    /// Id of the current tick, or of the last one if no tick is active.
+    pub fn tick_id(&self) -> u64 {
+        self.tick_id
+    }
+
+    pub fn set_value<V: Into<Value>>(
         &mut self,
         node: NodeId,
-        value: S,
+        value: V,
     ) -> Result<(), SchedulerError> {
-        let value = value.into();
-        self.store.set_value(node, value.clone());
+        self.set_value_in(Lane::Input, node, value)
+    }
+
+    pub fn set_value_in<V: Into<Value>>(
+        &mut self,
+        lane: Lane,
+        node: NodeId,
+        value: V,
+    ) -> Result<(), SchedulerError> {
+        if !self.scheduler.is_active() {
+            return Err(SchedulerError::TickNotStarted);
+        }
+        self.write_value(lane, node, value.into(), None)
+    }
+
+    /// Runs `f` against a transaction over the store and applies its writes
+    /// if it returns `Ok`. On `Err` every write it staged is dropped.
+    ///
+    /// Writes made with `Transaction::set_field`, `push` or `splice` only
+    /// dirty the paths they touched, so selectors that read other paths of
+    /// the same node through `SelectorContext::read_path` do not re-run.
+    pub fn update<F, R, E>(&mut self, f: F) -> Result<Result<R, E>, SchedulerError>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<R, E>,
+    {
+        self.update_in(Lane::Input, f)
+    }
+
+    pub fn update_in<F, R, E>(&mut self, lane: Lane, f: F) -> Result<Result<R, E>, SchedulerError>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<R, E>,
+    {
+        if !self.scheduler.is_active() {
+            return Err(SchedulerError::TickNotStarted);
+        }
+        let mut transaction = Transaction::new(&self.store);
+        let result = f(&mut transaction);
+        if result.is_ok() {
+            for (node, write) in transaction.into_writes() {
+                self.write_value(lane, node, write.value, write.paths)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Writes `value` to the store and queues its text, marking `paths` (or
+    /// the whole node for `None`) dirty.
+    fn write_value(
+        &mut self,
+        lane: Lane,
+        node: NodeId,
+        value: Value,
+        paths: Option<BTreeSet<Path>>,
+    ) -> Result<(), SchedulerError> {
+        if self.store.get_value(node) == Some(&value) {
+            self.telemetry.record_skipped_patches(1);
+            return Ok(());
+        }
+        let text = value.to_text();
         self.scheduler
-            .enqueue_op(PatchOp::SetText { node, text: value })
+            .enqueue_op_in(lane, PatchOp::SetText { node, text })?;
+        if !self.write_log.contains_key(&node) {
+            let entry = UndoEntry {
+                was_dirty: self.dirty.contains(&node),
+                dirty_paths: self.dirty_paths.get(&node).cloned(),
+            };
+            self.write_log.insert(node, entry);
+        }
+        self.store.set_value(node, value);
+        let was_dirty = !self.dirty.insert(node);
+        match paths {
+            // Already dirty as a whole.
+            Some(_) if was_dirty && !self.dirty_paths.contains_key(&node) => {}
+            Some(paths) => self.dirty_paths.entry(node).or_default().extend(paths),
+            None => {
+                self.dirty_paths.remove(&node);
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues a structural op (node creation, attributes, tree edits) in the
+    /// input lane.
+    pub fn enqueue_op(&mut self, op: PatchOp) -> Result<(), SchedulerError> {
+        self.scheduler.enqueue_op(op)
+    }
+
+    pub fn enqueue_op_in(&mut self, lane: Lane, op: PatchOp) -> Result<(), SchedulerError> {
+        self.scheduler.enqueue_op_in(lane, op)
     }
 
     pub fn commit(&mut self) -> Result<PatchBatch, SchedulerError> {
-        let batch = self.scheduler.commit_tick()?;
+        self.commit_with_budget(TickBudget::unlimited())
+    }
+
+    /// Commits the tick, carrying lower-priority lanes that exceed `budget`
+    /// over to later ticks.
+    ///
+    /// If the selectors to recompute read each other in a cycle, the tick ends
+    /// in a fallback batch naming the cycle, whether the cycle was known
+    /// before the tick or formed by reads made while evaluating it. A
+    /// selector that fails or panics also turns the tick into a fallback, and
+    /// every write made since `begin_tick` is undone. The failing selector is
+    /// then quarantined, so later ticks commit without it until a node
+    /// upstream of it is written.
+    pub fn commit_with_budget(&mut self, budget: TickBudget) -> Result<PatchBatch, SchedulerError> {
+        if !self.scheduler.is_active() {
+            return Err(SchedulerError::TickNotStarted);
+        }
+        self.release_quarantined();
+        let order = match self.dirty_selectors_in_order() {
+            Ok(order) => order,
+            Err(GraphError::Cycle(path)) => return self.fallback(cycle_reason(&path)),
+        };
+        let dirty = self.dirty.clone();
+        let dirty_paths = self.dirty_paths.clone();
+        let deferred_selectors = match self.recompute_selectors(order, &budget) {
+            Ok(deferred) => deferred,
+            Err(RecomputeError::Scheduler(err)) => return Err(err),
+            Err(RecomputeError::Graph(GraphError::Cycle(path))) => {
+                self.dirty = dirty;
+                self.dirty_paths = dirty_paths;
+                return self.fallback(cycle_reason(&path));
+            }
+            Err(RecomputeError::Selector(id, err)) => {
+                self.dirty = dirty;
+                self.dirty_paths = dirty_paths;
+                let reason = selector_failure_reason(id, &err);
+                self.abandon_tick(TickResult::Fallback, reason.clone(), Some("script"))?;
+                self.quarantine(id);
+                return Ok(self.stamp(PatchBatch::fallback(reason)));
+            }
+        };
+        self.write_log.clear();
+        self.checkpoint = None;
+        // Deferred selectors have not reached their binding yet.
+        self.unpublished.retain(|id| self.dirty.contains(id));
+        let ops = self.scheduler.commit_tick_with_budget(budget.max_ops())?;
+        let batch = self.stamp(PatchBatch::commit(ops).with_fingerprint());
+        self.telemetry
+            .record_deferred_work(deferred_selectors, self.scheduler.pending_len());
         self.telemetry.record_patch(&batch);
         self.telemetry.finalize_tick(TickResult::Commit);
         Ok(batch)
     }
 
+    /// Abandons the tick and returns an empty rollback batch for the host.
+    pub fn rollback(&mut self, reason: impl Into<String>) -> Result<PatchBatch, SchedulerError> {
+        let reason = reason.into();
+        self.rollback_tick(reason.clone())?;
+        Ok(self.stamp(PatchBatch::rollback(reason)))
+    }
+
+    /// Discards the ops queued and the store writes made since `begin_tick`,
+    /// recording the rollback in telemetry.
+    pub fn rollback_tick(&mut self, reason: impl Into<String>) -> Result<(), SchedulerError> {
+        self.abandon_tick(TickResult::Rollback, reason.into(), None)
+    }
+
+    /// Abandons the tick and tells the host to fall back to its own rendering.
+    pub fn fallback(&mut self, reason: impl Into<String>) -> Result<PatchBatch, SchedulerError> {
+        let reason = reason.into();
+        self.abandon_tick(TickResult::Fallback, reason.clone(), None)?;
+        Ok(self.stamp(PatchBatch::fallback(reason)))
+    }
+
+    fn abandon_tick(
+        &mut self,
+        kind: TickResult,
+        reason: String,
+        phase: Option<&str>,
+    ) -> Result<(), SchedulerError> {
+        self.scheduler.abort_tick()?;
+        if let Some(checkpoint) = self.checkpoint.take() {
+            self.store.restore(&checkpoint);
+        }
+        for (node, entry) in std::mem::take(&mut self.write_log) {
+            if !entry.was_dirty {
+                self.dirty.remove(&node);
+            }
+            match entry.dirty_paths {
+                Some(paths) => {
+                    self.dirty_paths.insert(node, paths);
+                }
+                None => {
+                    self.dirty_paths.remove(&node);
+                }
+            }
+        }
+        self.telemetry
+            .record_guardrail(GuardrailEvent::new(reason, phase.map(String::from), kind));
+        self.telemetry.finalize_tick(kind);
+        Ok(())
+    }
+
+    fn quarantine(&mut self, id: NodeId) {
+        self.dirty.remove(&id);
+        self.dirty_paths.remove(&id);
+        if let Some(entry) = self.selectors.remove(&id) {
+            self.quarantined.insert(id, entry);
+        }
+    }
+
+    /// Gives quarantined selectors downstream of this tick's writes another
+    /// run.
+    fn release_quarantined(&mut self) {
+        if self.quarantined.is_empty() {
+            return;
+        }
+        let mut affected = BTreeSet::new();
+        for &node in self.write_log.keys() {
+            affected.insert(node);
+            affected.extend(self.graph.transitive_dependents(node));
+        }
+        let released: Vec<NodeId> = self
+            .quarantined
+            .keys()
+            .copied()
+            .filter(|id| affected.contains(id))
+            .collect();
+        for id in released {
+            if let Some(entry) = self.quarantined.remove(&id) {
+                self.selectors.insert(id, entry);
+                self.dirty.insert(id);
+            }
+        }
+    }
+
+    fn stamp(&mut self, batch: PatchBatch) -> PatchBatch {
+        let batch_id = self.next_batch_id;
+        self.next_batch_id += 1;
+        batch.with_batch_id(batch_id).with_tick_id(self.tick_id)
+    }
+
     pub fn telemetry(&self) -> &TelemetryRecorder {
         &self.telemetry
     }
@@ -59,4 +620,208 @@ impl Engine {
     pub fn store(&self) -> &Store {
         &self.store
     }
+
+    pub fn graph(&self) -> &DependencyGraph {
+        &self.graph
+    }
+
+    /// Whether dirty selectors or carried ops are waiting for a later tick.
+    pub fn has_pending_work(&self) -> bool {
+        !self.dirty.is_empty() || self.scheduler.has_pending()
+    }
+
+    /// Re-evaluates dirty selectors in dependency order.
+    ///
+    /// A selector only runs when something it reads changed during this pass, so
+    /// an output equal to the stored value cuts propagation off at that node.
+    /// When `budget` runs out the rest of the order is put back into the dirty
+    /// set; since evaluation follows dependency order, every selector that did
+    /// run saw up-to-date inputs. Returns the number of deferred selectors.
+    fn recompute_selectors(
+        &mut self,
+        order: Vec<NodeId>,
+        budget: &TickBudget,
+    ) -> Result<usize, RecomputeError> {
+        let started = budget.max_duration().map(|limit| (Instant::now(), limit));
+        let mut pending = std::mem::take(&mut self.dirty);
+        let dirty_paths = std::mem::take(&mut self.dirty_paths);
+        for node in pending.clone() {
+            if self.selectors.contains_key(&node) {
+                continue;
+            }
+            // Skip dependents that only read paths this tick left untouched.
+            let changed = dirty_paths.get(&node);
+            pending.extend(
+                self.graph
+                    .dependents_of(node)
+                    .into_iter()
+                    .filter(|&dependent| {
+                        changed.is_none_or(|paths| self.graph.reads_changed(node, dependent, paths))
+                    }),
+            );
+        }
+        let mut run = Recompute {
+            selectors: &self.selectors,
+            recorder: Some(&mut self.telemetry),
+            pending,
+            in_progress: Vec::new(),
+            outputs: BTreeMap::new(),
+            published: BTreeSet::new(),
+            failure: None,
+            resolutions: &self.resolutions,
+            went_pending: BTreeMap::new(),
+            next_request: &mut self.next_request,
+            previous_reads: BTreeMap::new(),
+        };
+
+        let mut skipped = 0;
+        let mut deferred = 0;
+        for id in order {
+            if !run.pending.contains(&id) {
+                continue;
+            }
+            // Selectors already computed on demand still get their patch.
+            if !run.outputs.contains_key(&id) {
+                // Work carried over from earlier ticks flushes on its own
+                // schedule and must not starve this tick's selectors.
+                let out_of_ops = self.scheduler.enqueued_this_tick() >= budget.max_ops();
+                let out_of_time = started.is_some_and(|(start, limit)| start.elapsed() >= limit);
+                if out_of_ops || out_of_time {
+                    self.dirty.insert(id);
+                    deferred += 1;
+                    continue;
+                }
+            }
+            let output = run.output(&self.store, &mut self.graph, id);
+            if let Some(failure) = run.failure.take() {
+                // Edges recorded against the discarded writes go too. A failed
+                // selector keeps the reads of its failed run, which decide
+                // when it leaves quarantine.
+                let failed = match failure {
+                    RecomputeError::Selector(failed, _) => Some(failed),
+                    _ => None,
+                };
+                for (id, reads) in run.previous_reads {
+                    if Some(id) != failed {
+                        self.graph.set_dependency_reads(id, reads);
+                    }
+                }
+                return Err(failure);
+            }
+            let Some(output) = output else {
+                continue;
+            };
+            run.published.insert(id);
+            let entry = &self.selectors[&id];
+            let rebound = self.unpublished.contains(&id);
+            match publish(
+                &mut self.store,
+                &mut self.scheduler,
+                entry,
+                id,
+                output,
+                rebound,
+            )? {
+                Published::Changed => run.pending.extend(self.graph.dependents_of(id)),
+                Published::Rebound => {}
+                Published::Unchanged => skipped += 1,
+            }
+        }
+
+        let evaluated: Vec<NodeId> = run.outputs.keys().copied().collect();
+        let went_pending = std::mem::take(&mut run.went_pending);
+
+        // Selectors computed on demand outside the planned order, e.g. ones a
+        // deferred selector was going to read, are applied as well.
+        for (id, output) in run.outputs {
+            if run.published.contains(&id) {
+                continue;
+            }
+            if self.dirty.remove(&id) {
+                deferred -= 1;
+            }
+            let entry = &self.selectors[&id];
+            let rebound = self.unpublished.contains(&id);
+            match publish(
+                &mut self.store,
+                &mut self.scheduler,
+                entry,
+                id,
+                output,
+                rebound,
+            )? {
+                Published::Changed => self.dirty.extend(self.graph.dependents_of(id)),
+                Published::Rebound => {}
+                Published::Unchanged => skipped += 1,
+            }
+        }
+        for id in evaluated {
+            self.resolutions.remove(&id);
+            if let Some(&token) = went_pending.get(&id) {
+                self.awaiting.insert(id, token);
+            } else {
+                self.awaiting.remove(&id);
+            }
+        }
+        self.telemetry.record_skipped_patches(skipped);
+        Ok(deferred)
+    }
+
+    /// Collects every registered selector reachable from the dirty set and
+    /// orders them so each selector runs after the selectors it reads.
+    fn dirty_selectors_in_order(&self) -> Result<Vec<NodeId>, GraphError> {
+        let mut affected = BTreeSet::new();
+        for &node in &self.dirty {
+            affected.insert(node);
+            affected.extend(self.graph.transitive_dependents(node));
+        }
+        affected.retain(|node| self.selectors.contains_key(node));
+
+        // Among selectors whose inputs are ready, more urgent lanes go first so a
+        // budget cut defers background work before input work.
+        self.graph
+            .topological_order_by(&affected, |node| self.selectors[&node].lane)
+    }
+}
+
+/// What `publish` did with a selector output.
+enum Published {
+    Changed,
+    /// Unchanged, but queued anyway for a binding that has not shown it yet.
+    Rebound,
+    Unchanged,
+}
+
+/// Stores a selector output and queues the op for its binding, unless the
+/// output is unchanged and `rebound` is false.
+fn publish(
+    store: &mut Store,
+    scheduler: &mut Scheduler,
+    entry: &RegisteredSelector,
+    id: NodeId,
+    output: Value,
+    rebound: bool,
+) -> Result<Published, SchedulerError> {
+    let changed = store.get_value(id) != Some(&output);
+    if !changed && !rebound {
+        return Ok(Published::Unchanged);
+    }
+    scheduler.enqueue_op_in(entry.lane, entry.binding.op_for(&output))?;
+    if !changed {
+        return Ok(Published::Rebound);
+    }
+    store.set_value(id, output);
+    Ok(Published::Changed)
+}
+
+fn selector_failure_reason(id: NodeId, err: &SelectorError) -> String {
+    match err {
+        SelectorError::Failed(message) => format!("selector {} failed: {message}", id.raw()),
+        SelectorError::Panicked(message) => format!("selector {} panicked: {message}", id.raw()),
+    }
+}
+
+fn cycle_reason(path: &[NodeId]) -> String {
+    let path: Vec<String> = path.iter().map(|node| node.raw().to_string()).collect();
+    format!("selector cycle: {}", path.join(" -> "))
 }
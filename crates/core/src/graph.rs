@@ -1,10 +1,26 @@
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
 
+use crate::path::Path;
 use crate::NodeId;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// A dependency cycle, as the path `a -> b -> ... -> a`.
+    Cycle(Vec<NodeId>),
+}
+
+/// The sources a dependent reads, each with the paths it reads inside it
+/// (`None` for the whole value).
+pub(crate) type Reads = Vec<(NodeId, Option<BTreeSet<Path>>)>;
+
 #[derive(Debug, Default, Clone)]
 pub struct DependencyGraph {
     adjacency: BTreeMap<NodeId, BTreeSet<NodeId>>,
+    dependencies: BTreeMap<NodeId, BTreeSet<NodeId>>,
+    /// Paths a dependent read inside a source; edges without an entry read
+    /// the whole value.
+    read_paths: BTreeMap<(NodeId, NodeId), BTreeSet<Path>>,
 }
 
 impl DependencyGraph {
@@ -14,6 +30,7 @@ impl DependencyGraph {
 
     pub fn add_node(&mut self, node: NodeId) {
         self.adjacency.entry(node).or_default();
+        self.dependencies.entry(node).or_default();
     }
 
     pub fn add_edge(&mut self, source: NodeId, dependent: NodeId) {
@@ -22,6 +39,119 @@ impl DependencyGraph {
         if let Some(dependents) = self.adjacency.get_mut(&source) {
             dependents.insert(dependent);
         }
+        if let Some(sources) = self.dependencies.get_mut(&dependent) {
+            sources.insert(source);
+        }
+    }
+
+    /// Removes the edge `source -> dependent`, returning whether it existed.
+    pub fn remove_edge(&mut self, source: NodeId, dependent: NodeId) -> bool {
+        let removed = self
+            .adjacency
+            .get_mut(&source)
+            .is_some_and(|dependents| dependents.remove(&dependent));
+        if let Some(sources) = self.dependencies.get_mut(&dependent) {
+            sources.remove(&source);
+        }
+        self.read_paths.remove(&(source, dependent));
+        removed
+    }
+
+    /// Removes `node` along with every edge into or out of it.
+    pub fn remove_node(&mut self, node: NodeId) {
+        if let Some(dependents) = self.adjacency.remove(&node) {
+            for dependent in dependents {
+                if let Some(sources) = self.dependencies.get_mut(&dependent) {
+                    sources.remove(&node);
+                }
+            }
+        }
+        if let Some(sources) = self.dependencies.remove(&node) {
+            for source in sources {
+                if let Some(dependents) = self.adjacency.get_mut(&source) {
+                    dependents.remove(&node);
+                }
+            }
+        }
+        self.read_paths
+            .retain(|&(source, dependent), _| source != node && dependent != node);
+    }
+
+    /// Drops every edge into `dependent` whose source is not in `keep`.
+    ///
+    /// Selectors call this after an evaluation so reads that were not repeated
+    /// stop invalidating them.
+    pub fn retain_dependencies(&mut self, dependent: NodeId, keep: &BTreeSet<NodeId>) {
+        let stale: Vec<NodeId> = self
+            .dependencies
+            .get(&dependent)
+            .map(|sources| sources.difference(keep).copied().collect())
+            .unwrap_or_default();
+        for source in stale {
+            self.remove_edge(source, dependent);
+        }
+    }
+
+    /// Records which paths inside `source` the edge `source -> dependent`
+    /// reads; `None` means the whole value.
+    pub(crate) fn set_read_paths(
+        &mut self,
+        source: NodeId,
+        dependent: NodeId,
+        paths: Option<BTreeSet<Path>>,
+    ) {
+        match paths {
+            Some(paths) => {
+                self.read_paths.insert((source, dependent), paths);
+            }
+            None => {
+                self.read_paths.remove(&(source, dependent));
+            }
+        }
+    }
+
+    /// Every source `dependent` reads, with the paths it reads inside each.
+    pub(crate) fn dependency_reads(&self, dependent: NodeId) -> Reads {
+        self.dependencies_of(dependent)
+            .into_iter()
+            .map(|source| (source, self.read_paths(source, dependent).cloned()))
+            .collect()
+    }
+
+    /// Replaces the edges into `dependent` with `reads`, as returned by
+    /// `dependency_reads`.
+    pub(crate) fn set_dependency_reads(&mut self, dependent: NodeId, reads: Reads) {
+        let keep = reads.iter().map(|&(source, _)| source).collect();
+        self.retain_dependencies(dependent, &keep);
+        for (source, paths) in reads {
+            self.add_edge(source, dependent);
+            self.set_read_paths(source, dependent, paths);
+        }
+    }
+
+    /// The paths `dependent` reads inside `source`, or `None` if it reads the
+    /// whole value.
+    pub fn read_paths(&self, source: NodeId, dependent: NodeId) -> Option<&BTreeSet<Path>> {
+        self.read_paths.get(&(source, dependent))
+    }
+
+    /// Whether a change to `changed` paths inside `source` can affect what
+    /// `dependent` read.
+    pub fn reads_changed(
+        &self,
+        source: NodeId,
+        dependent: NodeId,
+        changed: &BTreeSet<Path>,
+    ) -> bool {
+        self.read_paths(source, dependent).is_none_or(|reads| {
+            reads
+                .iter()
+                .any(|read| changed.iter().any(|path| path.overlaps(read)))
+        })
+    }
+
+    pub fn contains_node(&self, node: NodeId) -> bool {
+        self.adjacency.contains_key(&node)
     }
 
     pub fn dependents_of(&self, node: NodeId) -> Vec<NodeId> {
@@ -30,4 +160,174 @@ impl DependencyGraph {
             .map(|set| set.iter().copied().collect())
             .unwrap_or_default()
     }
+
+    /// The nodes `node` reads, i.e. the sources of its incoming edges.
+    pub fn dependencies_of(&self, node: NodeId) -> Vec<NodeId> {
+        self.dependencies
+            .get(&node)
+            .map(|set| set.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.adjacency.values().map(BTreeSet::len).sum()
+    }
+
+    /// Every edge as `(source, dependent)`, ordered by source then dependent.
+    pub fn edges(&self) -> impl Iterator<Item = (NodeId, NodeId)> + '_ {
+        self.adjacency.iter().flat_map(|(&source, dependents)| {
+            dependents.iter().map(move |&dependent| (source, dependent))
+        })
+    }
+
+    /// Renders the graph in Graphviz DOT, edges pointing from source to
+    /// dependent.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph dependencies {\n");
+        for node in self.adjacency.keys() {
+            let _ = writeln!(out, "  {};", node.raw());
+        }
+        for (source, dependent) in self.edges() {
+            let _ = writeln!(out, "  {} -> {};", source.raw(), dependent.raw());
+        }
+        out.push('}');
+        out.push('\n');
+        out
+    }
+
+    /// Renders the graph as
+    /// `{"nodes":[1,2],"edges":[{"source":1,"dependent":2}]}`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (index, node) in self.adjacency.keys().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}", node.raw());
+        }
+        out.push_str("],\"edges\":[");
+        for (index, (source, dependent)) in self.edges().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"source\":{},\"dependent\":{}}}",
+                source.raw(),
+                dependent.raw()
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Every node reachable from `node` through dependent edges. `node` itself
+    /// is only included when it sits on a cycle.
+    pub fn transitive_dependents(&self, node: NodeId) -> BTreeSet<NodeId> {
+        let mut reached = BTreeSet::new();
+        let mut stack = vec![node];
+        while let Some(current) = stack.pop() {
+            for &dependent in self.adjacency.get(&current).into_iter().flatten() {
+                if reached.insert(dependent) {
+                    stack.push(dependent);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Orders `nodes` so every node comes after the nodes it depends on, using
+    /// only edges between members of the set. Ties break by node id.
+    pub fn topological_order(&self, nodes: &BTreeSet<NodeId>) -> Result<Vec<NodeId>, GraphError> {
+        self.topological_order_by(nodes, |node| node)
+    }
+
+    /// Like `topological_order`, but among ready nodes the one with the
+    /// smallest `priority` (then the smallest id) goes first.
+    pub fn topological_order_by<K, F>(
+        &self,
+        nodes: &BTreeSet<NodeId>,
+        priority: F,
+    ) -> Result<Vec<NodeId>, GraphError>
+    where
+        K: Ord,
+        F: Fn(NodeId) -> K,
+    {
+        let mut in_degree: BTreeMap<NodeId, usize> = nodes
+            .iter()
+            .map(|&node| {
+                let count = self
+                    .dependencies
+                    .get(&node)
+                    .map_or(0, |sources| sources.intersection(nodes).count());
+                (node, count)
+            })
+            .collect();
+
+        let mut ready: BTreeSet<(K, NodeId)> = in_degree
+            .iter()
+            .filter(|(_, &count)| count == 0)
+            .map(|(&node, _)| (priority(node), node))
+            .collect();
+        let mut order = Vec::with_capacity(nodes.len());
+        while let Some((_, node)) = ready.pop_first() {
+            order.push(node);
+            for &dependent in self.adjacency.get(&node).into_iter().flatten() {
+                if let Some(count) = in_degree.get_mut(&dependent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert((priority(dependent), dependent));
+                    }
+                }
+            }
+        }
+
+        if order.len() == nodes.len() {
+            return Ok(order);
+        }
+        let placed: BTreeSet<NodeId> = order.into_iter().collect();
+        let stuck: BTreeSet<NodeId> = nodes.difference(&placed).copied().collect();
+        Err(GraphError::Cycle(self.cycle_within(&stuck)))
+    }
+
+    /// Fails with the first cycle found, if the graph has any.
+    pub fn ensure_acyclic(&self) -> Result<(), GraphError> {
+        let nodes = self.adjacency.keys().copied().collect();
+        self.topological_order(&nodes).map(|_| ())
+    }
+
+    /// Walks dependency edges backwards inside `stuck`, the nodes Kahn's
+    /// algorithm could not place. Each of them has a source in the set, so
+    /// the walk must revisit a node, closing a cycle.
+    fn cycle_within(&self, stuck: &BTreeSet<NodeId>) -> Vec<NodeId> {
+        let Some(&start) = stuck.first() else {
+            return Vec::new();
+        };
+        let mut walk = vec![start];
+        let mut current = start;
+        loop {
+            let Some(&source) = self
+                .dependencies
+                .get(&current)
+                .and_then(|sources| sources.intersection(stuck).next())
+            else {
+                return walk;
+            };
+            if let Some(index) = walk.iter().position(|&node| node == source) {
+                let mut cycle: Vec<NodeId> = walk[index..].to_vec();
+                cycle.reverse();
+                // Start at the smallest id so the same cycle always reads the same.
+                let smallest = (0..cycle.len()).min_by_key(|&at| cycle[at]).unwrap_or(0);
+                cycle.rotate_left(smallest);
+                cycle.push(cycle[0]);
+                return cycle;
+            }
+            walk.push(source);
+            current = source;
+        }
+    }
 }
@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::patch::PatchOp;
+use crate::NodeId;
+
+/// Hands out node ids and keeps string keys (e.g. component paths) bound to
+/// the same id across ticks.
+///
+/// Allocation is deterministic: fresh ids grow monotonically and recycled ids
+/// are reused smallest first, so replaying the same calls yields the same ids.
+/// Released ids only become reusable after `reclaim`, which callers invoke
+/// once the batch removing them has been committed to the host.
+///
+/// `release_removed` also follows the inserts in the batches it is given, so
+/// a `Remove`, which destroys the node's descendants on the host, releases
+/// the whole subtree.
+#[derive(Debug, Clone)]
+pub struct NodeIdAllocator {
+    first: u64,
+    next: u64,
+    keys: BTreeMap<String, NodeId>,
+    key_of: BTreeMap<NodeId, String>,
+    released: BTreeSet<NodeId>,
+    free: BTreeSet<NodeId>,
+    parent_of: BTreeMap<NodeId, NodeId>,
+    children: BTreeMap<NodeId, BTreeSet<NodeId>>,
+}
+
+impl NodeIdAllocator {
+    /// Starts allocating at 1, leaving 0 for the host root.
+    pub fn new() -> Self {
+        Self::starting_at(1)
+    }
+
+    pub fn starting_at(first: u64) -> Self {
+        Self {
+            first,
+            next: first,
+            keys: BTreeMap::new(),
+            key_of: BTreeMap::new(),
+            released: BTreeSet::new(),
+            free: BTreeSet::new(),
+            parent_of: BTreeMap::new(),
+            children: BTreeMap::new(),
+        }
+    }
+
+    /// Returns an unkeyed id, reusing the smallest reclaimed id if any.
+    pub fn allocate(&mut self) -> NodeId {
+        if let Some(node) = self.free.pop_first() {
+            return node;
+        }
+        let node = NodeId::new(self.next);
+        self.next += 1;
+        node
+    }
+
+    /// Returns the id bound to `key`, allocating and binding one on first use.
+    pub fn id_for_key(&mut self, key: &str) -> NodeId {
+        if let Some(&node) = self.keys.get(key) {
+            return node;
+        }
+        let node = self.allocate();
+        self.keys.insert(key.to_string(), node);
+        self.key_of.insert(node, key.to_string());
+        node
+    }
+
+    pub fn get(&self, key: &str) -> Option<NodeId> {
+        self.keys.get(key).copied()
+    }
+
+    pub fn key_of(&self, node: NodeId) -> Option<&str> {
+        self.key_of.get(&node).map(String::as_str)
+    }
+
+    /// Unbinds `node` from its key and queues it for reuse after `reclaim`.
+    /// Its descendants are not released; they are only forgotten as its
+    /// children.
+    ///
+    /// Returns `false` if the id was never handed out or is already released.
+    pub fn release(&mut self, node: NodeId) -> bool {
+        let handed_out = (self.first..self.next).contains(&node.raw());
+        if !handed_out || self.free.contains(&node) {
+            return false;
+        }
+        if let Some(key) = self.key_of.remove(&node) {
+            self.keys.remove(&key);
+        }
+        self.detach(node);
+        for child in self.children.remove(&node).unwrap_or_default() {
+            self.parent_of.remove(&child);
+        }
+        self.released.insert(node)
+    }
+
+    /// Replays a committed batch: inserts record where nodes are attached and
+    /// each `Remove` releases its node together with every descendant.
+    ///
+    /// Pass every committed batch, not just its removals, so descendants are
+    /// known when their ancestor is removed.
+    pub fn release_removed<'a>(&mut self, ops: impl IntoIterator<Item = &'a PatchOp>) {
+        for op in ops {
+            match op {
+                PatchOp::Insert { parent, child } | PatchOp::InsertBefore { parent, child, .. } => {
+                    self.detach(*child);
+                    self.parent_of.insert(*child, *parent);
+                    self.children.entry(*parent).or_default().insert(*child);
+                }
+                PatchOp::Remove { node } => {
+                    for node in self.subtree(*node) {
+                        self.release(node);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `node` followed by every descendant recorded under it.
+    fn subtree(&self, node: NodeId) -> Vec<NodeId> {
+        let mut nodes = vec![node];
+        let mut next = 0;
+        while let Some(&parent) = nodes.get(next) {
+            next += 1;
+            if let Some(children) = self.children.get(&parent) {
+                nodes.extend(children.iter().copied());
+            }
+        }
+        nodes
+    }
+
+    fn detach(&mut self, node: NodeId) {
+        if let Some(parent) = self.parent_of.remove(&node) {
+            if let Some(siblings) = self.children.get_mut(&parent) {
+                siblings.remove(&node);
+            }
+        }
+    }
+
+    /// Makes every released id available to `allocate` again.
+    pub fn reclaim(&mut self) {
+        self.free.append(&mut self.released);
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl Default for NodeIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::patch::PatchOp;
+use crate::NodeId;
+
+/// One child in a keyed list: the stable key and the node rendering it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyedChild<K> {
+    pub key: K,
+    pub node: NodeId,
+}
+
+impl<K> KeyedChild<K> {
+    pub fn new(key: K, node: NodeId) -> Self {
+        Self { key, node }
+    }
+}
+
+/// Diffs the keyed children of `parent` and returns the ops that turn `old`
+/// into `new`.
+///
+/// Children whose key disappeared (or whose key now maps to a different node)
+/// are removed first. The children that keep their relative order form the
+/// longest increasing subsequence of old positions and stay put; every other
+/// child is moved or inserted with `InsertBefore`, or `Insert` when it becomes
+/// the last child. This is the minimal number of moves.
+///
+/// New nodes must already exist on the host (e.g. via `EnsureNode`). Keys are
+/// expected to be unique within each list.
+pub fn diff_keyed_children<K>(
+    parent: NodeId,
+    old: &[KeyedChild<K>],
+    new: &[KeyedChild<K>],
+) -> Vec<PatchOp>
+where
+    K: Eq + Hash,
+{
+    let old_index: HashMap<&K, usize> = old
+        .iter()
+        .enumerate()
+        .map(|(index, child)| (&child.key, index))
+        .collect();
+
+    // Position in `old` of each new child that is kept, `None` for fresh ones.
+    let sources: Vec<Option<usize>> = new
+        .iter()
+        .map(|child| {
+            old_index
+                .get(&child.key)
+                .copied()
+                .filter(|&index| old[index].node == child.node)
+        })
+        .collect();
+
+    let mut kept = vec![false; old.len()];
+    for index in sources.iter().flatten() {
+        kept[*index] = true;
+    }
+
+    let mut ops: Vec<PatchOp> = old
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| !**kept)
+        .map(|(child, _)| PatchOp::Remove { node: child.node })
+        .collect();
+
+    let stable = longest_increasing_subsequence(&sources);
+    let mut placements = Vec::new();
+    let mut next: Option<NodeId> = None;
+    for (index, child) in new.iter().enumerate().rev() {
+        if !stable[index] {
+            placements.push(match next {
+                Some(before) => PatchOp::InsertBefore {
+                    parent,
+                    child: child.node,
+                    before,
+                },
+                None => PatchOp::Insert {
+                    parent,
+                    child: child.node,
+                },
+            });
+        }
+        next = Some(child.node);
+    }
+    ops.extend(placements);
+    ops
+}
+
+/// Marks the entries of `sources` that belong to a longest strictly
+/// increasing subsequence; `None` entries are never part of it.
+fn longest_increasing_subsequence(sources: &[Option<usize>]) -> Vec<bool> {
+    // `tails[len]` is the index into `sources` of the smallest tail of an
+    // increasing run of length `len + 1`.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; sources.len()];
+    for (index, source) in sources.iter().enumerate() {
+        let Some(value) = source else {
+            continue;
+        };
+        let position = tails.partition_point(|&tail| sources[tail] < Some(*value));
+        if position > 0 {
+            previous[index] = Some(tails[position - 1]);
+        }
+        if position == tails.len() {
+            tails.push(index);
+        } else {
+            tails[position] = index;
+        }
+    }
+
+    let mut stable = vec![false; sources.len()];
+    let mut cursor = tails.last().copied();
+    while let Some(index) = cursor {
+        stable[index] = true;
+        cursor = previous[index];
+    }
+    stable
+}
@@ -1,21 +1,39 @@
 mod effects;
 mod engine;
 mod graph;
+mod identity;
+mod keyed;
 mod patch;
+mod path;
+mod persistent;
 mod scheduler;
 mod selector;
 mod store;
 mod telemetry;
+mod transaction;
 mod types;
+mod value;
+mod virtualizer;
+mod wire;
 
 pub use effects::EffectQueue;
-pub use engine::Engine;
-pub use graph::DependencyGraph;
+pub use engine::{Engine, TickBudget};
+pub use graph::{DependencyGraph, GraphError};
+pub use identity::NodeIdAllocator;
+pub use keyed::{diff_keyed_children, KeyedChild};
 pub use patch::{PatchBatch, PatchOp};
-pub use scheduler::{Scheduler, SchedulerError};
-pub use selector::{Selector, SelectorContext};
-pub use store::Store;
+pub use path::{Path, PathError, PathSegment};
+pub use scheduler::{Lane, Scheduler, SchedulerError};
+pub use selector::{
+    Binding, BindingTarget, BoxedSelector, PendingToken, Selector, SelectorContext, SelectorError,
+    SelectorFn, SelectorOutput,
+};
+pub use store::{Store, StoreSnapshot};
 pub use telemetry::{
     GuardrailEvent, PhaseDurations, TelemetryRecorder, TickResult, TickTelemetry, WorkBreakdown,
 };
+pub use transaction::Transaction;
 pub use types::NodeId;
+pub use value::Value;
+pub use virtualizer::{VirtualRow, Virtualizer};
+pub use wire::{decode_batch, encode_batch, WireError, WIRE_MAGIC, WIRE_VERSION};
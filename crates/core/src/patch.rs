@@ -1,23 +1,236 @@
+use crate::telemetry::TickResult;
 use crate::NodeId;
 
+/// A single host mutation.
+///
+/// Variants map onto the host contract in `docs/host-core-api.md`; `kind`
+/// returns the name the JS host dispatches on.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind"))]
 pub enum PatchOp {
+    EnsureNode {
+        #[cfg_attr(feature = "serde", serde(rename = "nodeId"))]
+        node: NodeId,
+        tag: String,
+    },
     SetText {
+        #[cfg_attr(feature = "serde", serde(rename = "nodeId"))]
         node: NodeId,
+        #[cfg_attr(feature = "serde", serde(rename = "value"))]
         text: String,
     },
     SetAttr {
+        #[cfg_attr(feature = "serde", serde(rename = "nodeId"))]
         node: NodeId,
         name: String,
         value: String,
     },
+    RemoveAttr {
+        #[cfg_attr(feature = "serde", serde(rename = "nodeId"))]
+        node: NodeId,
+        name: String,
+    },
+    #[cfg_attr(feature = "serde", serde(rename = "AppendChild"))]
     Insert {
+        #[cfg_attr(feature = "serde", serde(rename = "parentId"))]
+        parent: NodeId,
+        #[cfg_attr(feature = "serde", serde(rename = "childId"))]
+        child: NodeId,
+    },
+    InsertBefore {
+        #[cfg_attr(feature = "serde", serde(rename = "parentId"))]
         parent: NodeId,
+        #[cfg_attr(feature = "serde", serde(rename = "childId"))]
         child: NodeId,
+        #[cfg_attr(feature = "serde", serde(rename = "beforeId"))]
+        before: NodeId,
     },
     Remove {
+        #[cfg_attr(feature = "serde", serde(rename = "nodeId"))]
         node: NodeId,
     },
 }
 
-pub type PatchBatch = Vec<PatchOp>;
+impl PatchOp {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PatchOp::EnsureNode { .. } => "EnsureNode",
+            PatchOp::SetText { .. } => "SetText",
+            PatchOp::SetAttr { .. } => "SetAttr",
+            PatchOp::RemoveAttr { .. } => "RemoveAttr",
+            PatchOp::Insert { .. } => "AppendChild",
+            PatchOp::InsertBefore { .. } => "InsertBefore",
+            PatchOp::Remove { .. } => "Remove",
+        }
+    }
+
+    /// The node whose own content or attributes this op writes, if any.
+    pub fn target(&self) -> Option<NodeId> {
+        match self {
+            PatchOp::EnsureNode { node, .. }
+            | PatchOp::SetText { node, .. }
+            | PatchOp::SetAttr { node, .. }
+            | PatchOp::RemoveAttr { node, .. } => Some(*node),
+            PatchOp::Insert { .. } | PatchOp::InsertBefore { .. } | PatchOp::Remove { .. } => None,
+        }
+    }
+
+    /// Every node this op refers to.
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = NodeId> {
+        let nodes = match self {
+            PatchOp::Insert { parent, child } => [Some(*parent), Some(*child), None],
+            PatchOp::InsertBefore {
+                parent,
+                child,
+                before,
+            } => [Some(*parent), Some(*child), Some(*before)],
+            PatchOp::Remove { node } => [Some(*node), None, None],
+            _ => [self.target(), None, None],
+        };
+        nodes.into_iter().flatten()
+    }
+
+    /// Whether this op creates, moves or destroys a node rather than writing
+    /// to one.
+    pub(crate) fn is_structural(&self) -> bool {
+        matches!(
+            self,
+            PatchOp::EnsureNode { .. }
+                | PatchOp::Insert { .. }
+                | PatchOp::InsertBefore { .. }
+                | PatchOp::Remove { .. }
+        )
+    }
+}
+
+/// One tick's worth of ops plus the metadata the host contract carries.
+///
+/// `meta_kind` is `Commit` for batches the host applies; rollback and fallback
+/// batches never carry ops.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct PatchBatch {
+    pub meta_kind: TickResult,
+    pub ops: Vec<PatchOp>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub batch_id: Option<u64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub tick_id: Option<u64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub reason: Option<String>,
+    /// Serialized as a decimal string, since a `u64` does not fit in a JS
+    /// number.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "fingerprint_string"
+        )
+    )]
+    pub fingerprint: Option<u64>,
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod fingerprint_string {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        fingerprint: &Option<u64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match fingerprint {
+            Some(fingerprint) => serializer.collect_str(fingerprint),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|raw| raw.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+impl PatchBatch {
+    pub fn commit(ops: Vec<PatchOp>) -> Self {
+        Self {
+            meta_kind: TickResult::Commit,
+            ops,
+            ..Self::default()
+        }
+    }
+
+    pub fn rollback(reason: impl Into<String>) -> Self {
+        Self {
+            meta_kind: TickResult::Rollback,
+            reason: Some(reason.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn fallback(reason: impl Into<String>) -> Self {
+        Self {
+            meta_kind: TickResult::Fallback,
+            reason: Some(reason.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_batch_id(mut self, batch_id: u64) -> Self {
+        self.batch_id = Some(batch_id);
+        self
+    }
+
+    pub fn with_tick_id(mut self, tick_id: u64) -> Self {
+        self.tick_id = Some(tick_id);
+        self
+    }
+
+    /// Stamps the batch with a hash of its ops, stable across builds and
+    /// platforms: FNV-1a 64 over the ops' wire encoding.
+    pub fn with_fingerprint(mut self) -> Self {
+        self.fingerprint = Some(fingerprint_ops(&self.ops));
+        self
+    }
+
+    pub fn is_commit(&self) -> bool {
+        self.meta_kind == TickResult::Commit
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, PatchOp> {
+        self.ops.iter()
+    }
+}
+
+pub(crate) fn fingerprint_ops(ops: &[PatchOp]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    crate::wire::encode_ops(ops)
+        .into_iter()
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        })
+}
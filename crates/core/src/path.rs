@@ -0,0 +1,199 @@
+use crate::Value;
+
+/// One step into a structured `Value`: a map key or a list index.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl PathSegment {
+    /// Whether both segments can select the same entry.
+    fn may_alias(&self, other: &PathSegment) -> bool {
+        match (self, other) {
+            (PathSegment::Key(key), PathSegment::Index(index))
+            | (PathSegment::Index(index), PathSegment::Key(key)) => *key == index.to_string(),
+            _ => self == other,
+        }
+    }
+}
+
+/// A location inside a node's value, e.g. `items.3.title`.
+///
+/// The empty path is the whole value.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Path(Vec<PathSegment>);
+
+impl Path {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.0.push(PathSegment::Key(key.into()));
+        self
+    }
+
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push(PathSegment::Index(index));
+        self
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn starts_with(&self, prefix: &Path) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// Whether a change at one path can affect a read at the other, i.e. one
+    /// is a prefix of the other. An index and the map key spelling it, such
+    /// as `3` and `"3"`, reach the same entry of a map and count as equal.
+    pub fn overlaps(&self, other: &Path) -> bool {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .all(|(ours, theirs)| ours.may_alias(theirs))
+    }
+
+    fn split_last(&self) -> Option<(Path, &PathSegment)> {
+        let (last, parent) = self.0.split_last()?;
+        Some((Path(parent.to_vec()), last))
+    }
+}
+
+/// Parses a dot-separated path; all-digit segments become list indices.
+impl From<&str> for Path {
+    fn from(raw: &str) -> Self {
+        let segments = raw
+            .split('.')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| match segment.parse() {
+                Ok(index) => PathSegment::Index(index),
+                Err(_) => PathSegment::Key(segment.to_string()),
+            })
+            .collect();
+        Self(segments)
+    }
+}
+
+impl From<Vec<PathSegment>> for Path {
+    fn from(segments: Vec<PathSegment>) -> Self {
+        Self(segments)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// Nothing exists at this path.
+    Missing(Path),
+    /// The value at this path has the wrong type for the operation.
+    TypeMismatch(Path),
+    /// A list index or splice range reaching past the end of the list.
+    OutOfRange(Path),
+}
+
+impl Value {
+    /// The value at `path`, if every segment resolves. Index segments also
+    /// look up the matching key in a map.
+    pub fn get_path(&self, path: &Path) -> Option<&Value> {
+        path.segments()
+            .iter()
+            .try_fold(self, |current, segment| match (current, segment) {
+                (Value::Map(entries), PathSegment::Key(key)) => entries.get(key),
+                (Value::Map(entries), PathSegment::Index(index)) => entries.get(&index.to_string()),
+                (Value::List(items), PathSegment::Index(index)) => items.get(*index),
+                _ => None,
+            })
+    }
+
+    fn get_path_mut(&mut self, path: &Path) -> Result<&mut Value, PathError> {
+        let mut current = self;
+        for (depth, segment) in path.segments().iter().enumerate() {
+            let next = match (current, segment) {
+                (Value::Map(entries), PathSegment::Key(key)) => entries.get_mut(key),
+                (Value::Map(entries), PathSegment::Index(index)) => {
+                    entries.get_mut(&index.to_string())
+                }
+                (Value::List(items), PathSegment::Index(index)) => items.get_mut(*index),
+                _ => None,
+            };
+            current =
+                next.ok_or_else(|| PathError::Missing(Path(path.segments()[..=depth].to_vec())))?;
+        }
+        Ok(current)
+    }
+
+    /// Writes `value` at `path`, replacing the whole value for the root path.
+    ///
+    /// The parent must exist; a missing key is added to a parent map (a null
+    /// parent becomes an empty map) and a list index must be in range.
+    pub fn set_path(&mut self, path: &Path, value: Value) -> Result<(), PathError> {
+        let Some((parent_path, last)) = path.split_last() else {
+            *self = value;
+            return Ok(());
+        };
+        let parent = self.get_path_mut(&parent_path)?;
+        if parent.is_null() {
+            *parent = Value::Map(Default::default());
+        }
+        match (parent, last) {
+            (Value::Map(entries), PathSegment::Key(key)) => {
+                entries.insert(key.clone(), value);
+            }
+            (Value::Map(entries), PathSegment::Index(index)) => {
+                entries.insert(index.to_string(), value);
+            }
+            (Value::List(items), PathSegment::Index(index)) => {
+                let slot = items
+                    .get_mut(*index)
+                    .ok_or_else(|| PathError::OutOfRange(path.clone()))?;
+                *slot = value;
+            }
+            _ => return Err(PathError::TypeMismatch(parent_path)),
+        }
+        Ok(())
+    }
+
+    /// Appends to the list at `path`, returning the new item's index. A null
+    /// value at `path` becomes an empty list first.
+    pub fn push_path(&mut self, path: &Path, value: Value) -> Result<usize, PathError> {
+        let items = list_at(self.get_path_mut(path)?, path)?;
+        items.push(value);
+        Ok(items.len() - 1)
+    }
+
+    /// Removes `delete` items from the list at `path` starting at `start`,
+    /// inserts `items` in their place and returns the removed items.
+    pub fn splice_path(
+        &mut self,
+        path: &Path,
+        start: usize,
+        delete: usize,
+        items: Vec<Value>,
+    ) -> Result<Vec<Value>, PathError> {
+        let target = self.get_path_mut(path)?;
+        let len = target.as_list().map_or(0, <[Value]>::len);
+        let end = start
+            .checked_add(delete)
+            .filter(|&end| end <= len)
+            .ok_or_else(|| PathError::OutOfRange(path.clone()))?;
+        let list = list_at(target, path)?;
+        Ok(list.splice(start..end, items).collect())
+    }
+}
+
+fn list_at<'v>(target: &'v mut Value, path: &Path) -> Result<&'v mut Vec<Value>, PathError> {
+    if target.is_null() {
+        *target = Value::List(Vec::new());
+    }
+    match target {
+        Value::List(items) => Ok(items),
+        _ => Err(PathError::TypeMismatch(path.clone())),
+    }
+}
@@ -0,0 +1,162 @@
+use std::mem;
+use std::sync::Arc;
+
+use crate::NodeId;
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+/// A persistent map from node ids, stored as a hash array mapped trie over
+/// the id bits.
+///
+/// Clones share structure, so cloning is O(1) and a write copies only the
+/// branches on the path to the changed entry.
+#[derive(Debug)]
+pub(crate) struct NodeMap<V> {
+    root: Arc<Branch<V>>,
+}
+
+#[derive(Debug)]
+struct Branch<V> {
+    bitmap: u32,
+    entries: Vec<Entry<V>>,
+}
+
+#[derive(Debug)]
+enum Entry<V> {
+    Leaf(NodeId, Arc<V>),
+    Branch(Arc<Branch<V>>),
+}
+
+impl<V> NodeMap<V> {
+    pub(crate) fn get(&self, key: NodeId) -> Option<&V> {
+        self.root.get(key, 0)
+    }
+
+    pub(crate) fn insert(&mut self, key: NodeId, value: V) {
+        Arc::make_mut(&mut self.root).insert(key, Arc::new(value), 0);
+    }
+}
+
+impl<V: Clone> NodeMap<V> {
+    pub(crate) fn remove(&mut self, key: NodeId) -> Option<V> {
+        // Checking first keeps a miss from copying shared branches.
+        self.get(key)?;
+        let removed = Arc::make_mut(&mut self.root).remove(key, 0)?;
+        Some(Arc::try_unwrap(removed).unwrap_or_else(|shared| (*shared).clone()))
+    }
+}
+
+impl<V> Branch<V> {
+    fn position(&self, bit: u32) -> usize {
+        (self.bitmap & (bit - 1)).count_ones() as usize
+    }
+
+    fn get(&self, key: NodeId, shift: u32) -> Option<&V> {
+        let bit = slot(key, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        match &self.entries[self.position(bit)] {
+            Entry::Leaf(existing, value) => (*existing == key).then_some(&**value),
+            Entry::Branch(branch) => branch.get(key, shift + BITS),
+        }
+    }
+
+    fn insert(&mut self, key: NodeId, value: Arc<V>, shift: u32) {
+        let bit = slot(key, shift);
+        let position = self.position(bit);
+        if self.bitmap & bit == 0 {
+            self.bitmap |= bit;
+            self.entries.insert(position, Entry::Leaf(key, value));
+            return;
+        }
+        let entry = &mut self.entries[position];
+        match entry {
+            Entry::Branch(branch) => Arc::make_mut(branch).insert(key, value, shift + BITS),
+            Entry::Leaf(existing, current) if *existing == key => *current = value,
+            Entry::Leaf(existing, current) => {
+                // Two ids share this slot; push both one level down.
+                let mut branch = Branch::default();
+                branch.insert(*existing, Arc::clone(current), shift + BITS);
+                branch.insert(key, value, shift + BITS);
+                *entry = Entry::Branch(Arc::new(branch));
+            }
+        }
+    }
+
+    fn remove(&mut self, key: NodeId, shift: u32) -> Option<Arc<V>> {
+        let bit = slot(key, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        let position = self.position(bit);
+        match &mut self.entries[position] {
+            Entry::Leaf(existing, _) if *existing != key => return None,
+            Entry::Leaf(..) => {}
+            Entry::Branch(branch) => {
+                let branch = Arc::make_mut(branch);
+                let removed = branch.remove(key, shift + BITS);
+                // A branch left holding one leaf is replaced by that leaf.
+                if let [Entry::Leaf(..)] = branch.entries.as_slice() {
+                    let leaf = mem::take(&mut branch.entries).swap_remove(0);
+                    self.entries[position] = leaf;
+                }
+                return removed;
+            }
+        }
+        self.bitmap &= !bit;
+        match self.entries.remove(position) {
+            Entry::Leaf(_, value) => Some(value),
+            Entry::Branch(_) => None,
+        }
+    }
+}
+
+/// The bitmap bit for `key` at the trie level starting at `shift`.
+fn slot(key: NodeId, shift: u32) -> u32 {
+    1 << ((key.raw() >> shift) & MASK)
+}
+
+impl<V> Clone for NodeMap<V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: Arc::clone(&self.root),
+        }
+    }
+}
+
+impl<V> Default for NodeMap<V> {
+    fn default() -> Self {
+        Self {
+            root: Arc::default(),
+        }
+    }
+}
+
+impl<V> Clone for Branch<V> {
+    fn clone(&self) -> Self {
+        Self {
+            bitmap: self.bitmap,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<V> Default for Branch<V> {
+    fn default() -> Self {
+        Self {
+            bitmap: 0,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<V> Clone for Entry<V> {
+    fn clone(&self) -> Self {
+        match self {
+            Entry::Leaf(key, value) => Entry::Leaf(*key, Arc::clone(value)),
+            Entry::Branch(branch) => Entry::Branch(Arc::clone(branch)),
+        }
+    }
+}
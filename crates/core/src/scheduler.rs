@@ -1,5 +1,8 @@
-use crate::effects::EffectQueue;
-use crate::patch::{PatchBatch, PatchOp};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::effects::{coalesce, WriteKey};
+use crate::patch::PatchOp;
+use crate::NodeId;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SchedulerError {
@@ -7,10 +10,44 @@ pub enum SchedulerError {
     TickNotStarted,
 }
 
+/// Priority lane an op or selector recomputation is scheduled in.
+///
+/// Lanes are ordered from most to least urgent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Lane {
+    #[default]
+    Input,
+    Animation,
+    Background,
+}
+
+impl Lane {
+    pub const ALL: [Lane; 3] = [Lane::Input, Lane::Animation, Lane::Background];
+}
+
 #[derive(Debug, Clone)]
 pub struct Scheduler {
     state: TickState,
-    queue: EffectQueue,
+    lanes: BTreeMap<Lane, LaneQueue>,
+    /// The lane and sequence number of the pending write to each slot.
+    writes: BTreeMap<WriteKey<'static>, (Lane, u64)>,
+    checkpoint: Option<Checkpoint>,
+    next_seq: u64,
+    enqueued: usize,
+}
+
+/// Ops waiting in one lane, keyed by their position in enqueue order across
+/// all lanes.
+#[derive(Debug, Clone, Default)]
+struct LaneQueue {
+    ops: BTreeMap<u64, PatchOp>,
+}
+
+/// Pending work as it was at `begin_tick`.
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    lanes: BTreeMap<Lane, LaneQueue>,
+    writes: BTreeMap<WriteKey<'static>, (Lane, u64)>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -23,7 +60,14 @@ impl Scheduler {
     pub fn new() -> Self {
         Self {
             state: TickState::Idle,
-            queue: EffectQueue::new(),
+            lanes: Lane::ALL
+                .into_iter()
+                .map(|lane| (lane, LaneQueue::default()))
+                .collect(),
+            writes: BTreeMap::new(),
+            checkpoint: None,
+            next_seq: 0,
+            enqueued: 0,
         }
     }
 
@@ -32,24 +76,173 @@ impl Scheduler {
             return Err(SchedulerError::TickAlreadyStarted);
         }
         self.state = TickState::Active;
+        self.checkpoint = Some(Checkpoint {
+            lanes: self.lanes.clone(),
+            writes: self.writes.clone(),
+        });
+        self.enqueued = 0;
         Ok(())
     }
 
+    /// Ends the tick without committing, discarding every op enqueued since
+    /// `begin_tick`. Work carried over from earlier ticks is kept.
+    pub fn abort_tick(&mut self) -> Result<(), SchedulerError> {
+        if self.state != TickState::Active {
+            return Err(SchedulerError::TickNotStarted);
+        }
+        self.state = TickState::Idle;
+        if let Some(checkpoint) = self.checkpoint.take() {
+            self.lanes = checkpoint.lanes;
+            self.writes = checkpoint.writes;
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == TickState::Active
+    }
+
     pub fn enqueue_op(&mut self, op: PatchOp) -> Result<(), SchedulerError> {
+        self.enqueue_op_in(Lane::Input, op)
+    }
+
+    pub fn enqueue_op_in(&mut self, lane: Lane, op: PatchOp) -> Result<(), SchedulerError> {
         if self.state != TickState::Active {
             return Err(SchedulerError::TickNotStarted);
         }
-        self.queue.push(op);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.enqueued += 1;
+        // A newer write wins even if an older one sits in a lane that flushes later.
+        if let Some(key) = WriteKey::of(&op) {
+            if let Some((older_lane, older)) = self.writes.insert(key.into_owned(), (lane, seq)) {
+                self.queue_mut(older_lane).ops.remove(&older);
+            }
+        }
+        self.queue_mut(lane).ops.insert(seq, op);
         Ok(())
     }
 
-    pub fn commit_tick(&mut self) -> Result<PatchBatch, SchedulerError> {
+    /// Ops waiting in `lane`, including work carried over from earlier ticks.
+    pub fn pending_in(&self, lane: Lane) -> Vec<&PatchOp> {
+        self.lanes[&lane].ops.values().collect()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.lanes.values().map(|queue| queue.ops.len()).sum()
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.lanes.values().any(|queue| !queue.ops.is_empty())
+    }
+
+    /// Ops enqueued since `begin_tick`, not counting carried-over work.
+    pub fn enqueued_this_tick(&self) -> usize {
+        self.enqueued
+    }
+
+    /// Commits every lane.
+    pub fn commit_tick(&mut self) -> Result<Vec<PatchOp>, SchedulerError> {
+        self.commit_tick_with_budget(usize::MAX)
+    }
+
+    /// Commits the input lane unconditionally, then fills what is left of
+    /// `max_ops` from the front of each lower lane, most urgent first.
+    ///
+    /// The rest of each lane is carried over to a later tick, except ops that
+    /// a flushed op depends on (an earlier structural op on the same node, or
+    /// an earlier write to a node a flushed structural op touches), which
+    /// flush with it. Flushed ops are coalesced together and keep their
+    /// enqueue order across lanes, so a node is created before any lane writes
+    /// to it.
+    pub fn commit_tick_with_budget(
+        &mut self,
+        max_ops: usize,
+    ) -> Result<Vec<PatchOp>, SchedulerError> {
         if self.state != TickState::Active {
             return Err(SchedulerError::TickNotStarted);
         }
         self.state = TickState::Idle;
-        Ok(self.queue.commit())
+        self.checkpoint = None;
+
+        let mut flush = BTreeSet::new();
+        let mut room = max_ops;
+        for (&lane, queue) in &self.lanes {
+            let take = if lane == Lane::Input {
+                queue.ops.len()
+            } else {
+                queue.ops.len().min(room)
+            };
+            room = room.saturating_sub(take);
+            flush.extend(queue.ops.keys().take(take));
+        }
+        self.pull_in_dependencies(&mut flush);
+
+        let mut flushed = BTreeMap::new();
+        for queue in self.lanes.values_mut() {
+            for &seq in &flush {
+                if let Some(op) = queue.ops.remove(&seq) {
+                    flushed.insert(seq, op);
+                }
+            }
+        }
+        self.writes.retain(|_, (_, seq)| !flush.contains(seq));
+        Ok(coalesce(flushed.into_values().collect()))
     }
+
+    /// Adds to `flush` the carried ops that an op in it depends on, i.e. an
+    /// earlier structural op on a node it touches or, for a structural op, an
+    /// earlier write to one of its nodes.
+    fn pull_in_dependencies(&self, flush: &mut BTreeSet<u64>) {
+        let mut ops = BTreeMap::new();
+        let mut structural: BTreeMap<NodeId, BTreeSet<u64>> = BTreeMap::new();
+        let mut writes: BTreeMap<NodeId, BTreeSet<u64>> = BTreeMap::new();
+        for queue in self.lanes.values() {
+            for (&seq, op) in &queue.ops {
+                ops.insert(seq, op);
+                if flush.contains(&seq) {
+                    continue;
+                }
+                let index = if op.is_structural() {
+                    &mut structural
+                } else {
+                    &mut writes
+                };
+                for node in op.nodes() {
+                    index.entry(node).or_default().insert(seq);
+                }
+            }
+        }
+
+        let mut work: Vec<u64> = flush.iter().copied().collect();
+        while let Some(seq) = work.pop() {
+            let op = ops[&seq];
+            for node in op.nodes() {
+                let mut earlier = take_before(&mut structural, node, seq);
+                if op.is_structural() {
+                    earlier.extend(take_before(&mut writes, node, seq));
+                }
+                for dependency in earlier {
+                    if flush.insert(dependency) {
+                        work.push(dependency);
+                    }
+                }
+            }
+        }
+    }
+
+    fn queue_mut(&mut self, lane: Lane) -> &mut LaneQueue {
+        self.lanes.entry(lane).or_default()
+    }
+}
+
+/// Removes and returns the entries of `node` in `index` that come before `seq`.
+fn take_before(index: &mut BTreeMap<NodeId, BTreeSet<u64>>, node: NodeId, seq: u64) -> Vec<u64> {
+    let Some(seqs) = index.get_mut(&node) else {
+        return Vec::new();
+    };
+    let later = seqs.split_off(&seq);
+    std::mem::replace(seqs, later).into_iter().collect()
 }
 
 impl Default for Scheduler {
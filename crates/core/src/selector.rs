@@ -1,14 +1,68 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::patch::PatchOp;
+use crate::path::Path;
 use crate::telemetry::TelemetryRecorder;
-use crate::{DependencyGraph, NodeId, Store};
+use crate::{DependencyGraph, NodeId, Store, Value};
 
 #[cfg(feature = "phase6-telemetry")]
 use std::time::Instant;
 
+/// Supplies up-to-date outputs for selectors that are read before the engine
+/// has recomputed them in the current tick.
+pub(crate) trait Resolver {
+    /// Returns the current output of `node` if it is a selector computed (or
+    /// computed now, on demand) this tick, or `None` to read the store.
+    fn resolve(
+        &mut self,
+        store: &Store,
+        graph: &mut DependencyGraph,
+        node: NodeId,
+    ) -> Option<Value>;
+
+    /// The value the host resolved a pending selector with, if any.
+    fn resolution(&self, node: NodeId) -> Option<Value>;
+
+    /// Records that selector `node` is waiting on asynchronous work and
+    /// returns the token the result must be resolved with.
+    fn mark_pending(&mut self, node: NodeId) -> PendingToken;
+}
+
+/// Identifies one wait of a selector on asynchronous work.
+///
+/// Each time a selector goes pending it gets a new token, so a result for an
+/// earlier request cannot be resolved into the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PendingToken {
+    selector: NodeId,
+    request: u64,
+}
+
+impl PendingToken {
+    pub(crate) fn new(selector: NodeId, request: u64) -> Self {
+        Self { selector, request }
+    }
+
+    pub fn selector(&self) -> NodeId {
+        self.selector
+    }
+}
+
 pub struct SelectorContext<'a> {
     store: &'a Store,
     graph: &'a mut DependencyGraph,
+    resolver: Option<&'a mut dyn Resolver>,
     selector_id: NodeId,
     read_count: usize,
+    read_set: BTreeSet<NodeId>,
+    whole_reads: BTreeSet<NodeId>,
+    path_reads: BTreeMap<NodeId, BTreeSet<Path>>,
+    pending: bool,
+    token: Option<PendingToken>,
 }
 
 impl<'a> SelectorContext<'a> {
@@ -16,43 +70,231 @@ impl<'a> SelectorContext<'a> {
         Self {
             store,
             graph,
+            resolver: None,
             selector_id,
             read_count: 0,
+            read_set: BTreeSet::new(),
+            whole_reads: BTreeSet::new(),
+            path_reads: BTreeMap::new(),
+            pending: false,
+            token: None,
         }
     }
 
-    pub fn read(&mut self, node: NodeId) -> Option<String> {
+    /// Reads a store value or another selector's output, recording the edge.
+    pub fn read(&mut self, node: NodeId) -> Option<Value> {
+        self.record_read(node);
+        self.whole_reads.insert(node);
+        match self.resolve(node) {
+            Some(value) => Some(value),
+            None => self.store.get_value(node).cloned(),
+        }
+    }
+
+    /// Reads the value at `path` inside `node`. The selector only re-runs
+    /// for `Engine::update` writes that touch an overlapping path.
+    pub fn read_path(&mut self, node: NodeId, path: impl Into<Path>) -> Option<Value> {
+        let path = path.into();
+        self.record_read(node);
+        let resolved = self.resolve(node);
+        let value = match &resolved {
+            Some(value) => value.get_path(&path).cloned(),
+            None => self
+                .store
+                .get_value(node)
+                .and_then(|value| value.get_path(&path))
+                .cloned(),
+        };
+        self.path_reads.entry(node).or_default().insert(path);
+        value
+    }
+
+    fn record_read(&mut self, node: NodeId) {
         self.graph.add_edge(node, self.selector_id);
         self.read_count += 1;
-        self.store.get_value(node).cloned()
+        self.read_set.insert(node);
+    }
+
+    fn resolve(&mut self, node: NodeId) -> Option<Value> {
+        let resolver = self.resolver.as_deref_mut()?;
+        resolver.resolve(self.store, self.graph, node)
     }
 
     pub fn reads(&self) -> usize {
         self.read_count
     }
+
+    /// Nodes read so far during this evaluation.
+    pub fn read_set(&self) -> &BTreeSet<NodeId> {
+        &self.read_set
+    }
+
+    /// Marks this evaluation as waiting on asynchronous work and returns
+    /// `placeholder` to use as the output until the host resolves it through
+    /// `Engine::resolve_selector`.
+    pub fn pending(&mut self, placeholder: impl Into<Value>) -> Value {
+        self.pending = true;
+        if let Some(resolver) = self.resolver.as_deref_mut() {
+            self.token = Some(resolver.mark_pending(self.selector_id));
+        }
+        placeholder.into()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// The token `Engine::resolve_selector` accepts for the request started
+    /// by `pending`, when running inside an engine.
+    pub fn pending_token(&self) -> Option<PendingToken> {
+        self.token
+    }
+
+    /// The value this selector was resolved with since it last went pending.
+    pub fn resolved(&self) -> Option<Value> {
+        self.resolver
+            .as_deref()
+            .and_then(|resolver| resolver.resolution(self.selector_id))
+    }
+
+    /// Ends the evaluation, dropping edges to nodes that were not read this time.
+    fn finish(mut self) {
+        self.graph
+            .retain_dependencies(self.selector_id, &self.read_set);
+        for &node in &self.read_set {
+            let paths = if self.whole_reads.contains(&node) {
+                None
+            } else {
+                self.path_reads.remove(&node)
+            };
+            self.graph.set_read_paths(node, self.selector_id, paths);
+        }
+    }
 }
 
-pub struct Selector<F>
-where
-    F: Fn(&mut SelectorContext<'_>) -> String,
-{
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorError {
+    /// The selector returned an error with this message.
+    Failed(String),
+    /// The selector panicked with this message.
+    Panicked(String),
+}
+
+impl SelectorError {
+    pub fn failed(message: impl Into<String>) -> Self {
+        Self::Failed(message.into())
+    }
+}
+
+/// What a selector closure may return: a value, or a `Result` for selectors
+/// that can fail.
+pub trait SelectorOutput {
+    fn into_result(self) -> Result<Value, SelectorError>;
+}
+
+impl<T: Into<Value>> SelectorOutput for T {
+    fn into_result(self) -> Result<Value, SelectorError> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Value>> SelectorOutput for Result<T, SelectorError> {
+    fn into_result(self) -> Result<Value, SelectorError> {
+        self.map(Into::into)
+    }
+}
+
+pub type SelectorFn = Box<dyn Fn(&mut SelectorContext<'_>) -> Result<Value, SelectorError>>;
+
+pub type BoxedSelector = Selector<SelectorFn>;
+
+/// The DOM property a selector output is written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingTarget {
+    Text,
+    Attr(String),
+}
+
+/// Where the engine sends a selector's output: a node and one of its
+/// properties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub node: NodeId,
+    pub target: BindingTarget,
+}
+
+impl Binding {
+    pub fn text(node: NodeId) -> Self {
+        Self {
+            node,
+            target: BindingTarget::Text,
+        }
+    }
+
+    pub fn attr(node: NodeId, name: impl Into<String>) -> Self {
+        Self {
+            node,
+            target: BindingTarget::Attr(name.into()),
+        }
+    }
+
+    /// The op that writes `output` to the bound property. A null output
+    /// removes a bound attribute.
+    pub fn op_for(&self, output: &Value) -> PatchOp {
+        match &self.target {
+            BindingTarget::Text => PatchOp::SetText {
+                node: self.node,
+                text: output.to_text(),
+            },
+            BindingTarget::Attr(name) if output.is_null() => PatchOp::RemoveAttr {
+                node: self.node,
+                name: name.clone(),
+            },
+            BindingTarget::Attr(name) => PatchOp::SetAttr {
+                node: self.node,
+                name: name.clone(),
+                value: output.to_text(),
+            },
+        }
+    }
+}
+
+pub struct Selector<F> {
     id: NodeId,
     compute: F,
+    cache: RefCell<Option<CachedOutput>>,
 }
 
-impl<F> Selector<F>
+/// The last output together with the store version of every node it read.
+#[derive(Debug, Clone)]
+struct CachedOutput {
+    versions: Vec<(NodeId, u64)>,
+    output: Value,
+}
+
+impl<F, O> Selector<F>
 where
-    F: Fn(&mut SelectorContext<'_>) -> String,
+    F: Fn(&mut SelectorContext<'_>) -> O,
+    O: SelectorOutput,
 {
     pub fn new(id: NodeId, compute: F) -> Self {
-        Self { id, compute }
+        Self {
+            id,
+            compute,
+            cache: RefCell::new(None),
+        }
     }
 
     pub fn id(&self) -> NodeId {
         self.id
     }
 
-    pub fn evaluate(&self, store: &Store, graph: &mut DependencyGraph) -> String {
+    /// Returns the cached output if nothing it read has changed since, and
+    /// runs the selector otherwise.
+    ///
+    /// Panics if the selector fails; `try_evaluate` reports the failure
+    /// instead.
+    pub fn evaluate(&self, store: &Store, graph: &mut DependencyGraph) -> Value {
         self.evaluate_with_recorder(store, graph, None)
     }
 
@@ -60,21 +302,157 @@ where
         &self,
         store: &Store,
         graph: &mut DependencyGraph,
-        #[cfg_attr(not(feature = "phase6-telemetry"), allow(unused_variables))] recorder: Option<
-            &mut TelemetryRecorder,
-        >,
-    ) -> String {
+        recorder: Option<&mut TelemetryRecorder>,
+    ) -> Value {
+        self.try_evaluate_with_recorder(store, graph, recorder)
+            .unwrap_or_else(|err| panic!("selector {} failed: {err:?}", self.id.raw()))
+    }
+
+    /// Like `evaluate`, but returns an error when the selector fails or
+    /// panics. A failed evaluation leaves the cached output untouched.
+    pub fn try_evaluate(
+        &self,
+        store: &Store,
+        graph: &mut DependencyGraph,
+    ) -> Result<Value, SelectorError> {
+        self.try_evaluate_with_recorder(store, graph, None)
+    }
+
+    pub fn try_evaluate_with_recorder(
+        &self,
+        store: &Store,
+        graph: &mut DependencyGraph,
+        mut recorder: Option<&mut TelemetryRecorder>,
+    ) -> Result<Value, SelectorError> {
+        let cached = self.cached_output(store, graph);
+        if let Some(recorder) = &mut recorder {
+            recorder.record_selector_cache(cached.is_some());
+        }
+        if let Some(output) = cached {
+            return Ok(output);
+        }
+        let evaluation = self.run(store, graph, recorder, None)?;
+        // A placeholder must not be served from the cache once resolved.
+        *self.cache.borrow_mut() = evaluation.versions.map(|versions| CachedOutput {
+            versions,
+            output: evaluation.output.clone(),
+        });
+        Ok(evaluation.output)
+    }
+
+    /// Runs the selector for the engine, bypassing the cache.
+    ///
+    /// The engine only re-runs selectors after something they read changed,
+    /// so the cache would never hit there.
+    pub(crate) fn try_evaluate_with_resolver<'a>(
+        &self,
+        store: &'a Store,
+        graph: &'a mut DependencyGraph,
+        recorder: Option<&mut TelemetryRecorder>,
+        resolver: &'a mut dyn Resolver,
+    ) -> Result<Value, SelectorError> {
+        self.run(store, graph, recorder, Some(resolver))
+            .map(|evaluation| evaluation.output)
+    }
+
+    /// Drops the cached output so the next evaluation runs the selector.
+    pub fn invalidate(&self) {
+        self.cache.borrow_mut().take();
+    }
+
+    fn run<'a>(
+        &self,
+        store: &'a Store,
+        graph: &'a mut DependencyGraph,
+        recorder: Option<&mut TelemetryRecorder>,
+        resolver: Option<&'a mut dyn Resolver>,
+    ) -> Result<Evaluation, SelectorError> {
         let mut ctx = SelectorContext::new(store, graph, self.id);
+        ctx.resolver = resolver;
         #[cfg(feature = "phase6-telemetry")]
         let start = Instant::now();
 
-        let output = (self.compute)(&mut ctx);
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| (self.compute)(&mut ctx).into_result()))
+                .unwrap_or_else(|payload| {
+                    Err(SelectorError::Panicked(panic_message(payload.as_ref())))
+                });
 
         #[cfg(feature = "phase6-telemetry")]
         if let Some(recorder) = recorder {
             recorder.record_selector_evaluation(start.elapsed(), ctx.reads());
         }
+        #[cfg(not(feature = "phase6-telemetry"))]
+        let _ = recorder;
+
+        let output = match result {
+            Ok(output) => output,
+            Err(err) => {
+                // Edges follow the reads of this run even when it failed, so
+                // a write to anything it read gives it another run.
+                ctx.finish();
+                return Err(err);
+            }
+        };
+        let versions = (!ctx.is_pending()).then(|| {
+            ctx.read_set()
+                .iter()
+                .map(|&node| (node, store.version(node)))
+                .collect()
+        });
+        ctx.finish();
+        Ok(Evaluation { output, versions })
+    }
+
+    fn cached_output(&self, store: &Store, graph: &mut DependencyGraph) -> Option<Value> {
+        let cache = self.cache.borrow();
+        let cached = cache.as_ref()?;
+        let current = cached
+            .versions
+            .iter()
+            .all(|&(node, version)| store.version(node) == version);
+        if !current {
+            return None;
+        }
+        for &(node, _) in &cached.versions {
+            graph.add_edge(node, self.id);
+        }
+        Some(cached.output.clone())
+    }
+}
+
+/// A finished run: the output and, unless it is a pending placeholder, the
+/// store version of every node it read.
+struct Evaluation {
+    output: Value,
+    versions: Option<Vec<(NodeId, u64)>>,
+}
+
+impl BoxedSelector {
+    /// Boxes `compute` so selectors with different closures can be stored
+    /// together.
+    pub fn boxed<C, O>(id: NodeId, compute: C) -> Self
+    where
+        C: Fn(&mut SelectorContext<'_>) -> O + 'static,
+        O: SelectorOutput,
+    {
+        let compute: SelectorFn = Box::new(move |ctx| compute(ctx).into_result());
+        Self::new(id, compute)
+    }
+}
+
+impl<F> fmt::Debug for Selector<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Selector").field("id", &self.id).finish()
+    }
+}
 
-        output
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "selector panicked".to_string()
     }
 }
@@ -1,10 +1,25 @@
-use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::NodeId;
+use crate::persistent::NodeMap;
+use crate::{NodeId, Value};
 
+/// Source of every store version, shared by all stores so that a version
+/// never refers to two different values, even across clones.
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// Node values, held in persistent maps so `snapshot` is O(1) and snapshots
+/// share structure with the live store.
 #[derive(Debug, Default, Clone)]
 pub struct Store {
-    values: HashMap<NodeId, String>,
+    values: NodeMap<Value>,
+    versions: NodeMap<u64>,
+}
+
+/// A read-only view of a `Store` at the moment `Store::snapshot` was called.
+#[derive(Debug, Clone)]
+pub struct StoreSnapshot {
+    values: NodeMap<Value>,
+    versions: NodeMap<u64>,
 }
 
 impl Store {
@@ -12,11 +27,64 @@ impl Store {
         Self::default()
     }
 
-    pub fn set_value<S: Into<String>>(&mut self, node: NodeId, value: S) {
-        self.values.insert(node, value.into());
+    /// Writes `value`, bumping the node's version unless it is unchanged.
+    pub fn set_value<V: Into<Value>>(&mut self, node: NodeId, value: V) {
+        let value = value.into();
+        if self.values.get(node) == Some(&value) {
+            return;
+        }
+        self.values.insert(node, value);
+        self.bump(node);
+    }
+
+    pub fn remove_value(&mut self, node: NodeId) -> Option<Value> {
+        let removed = self.values.remove(node);
+        if removed.is_some() {
+            self.bump(node);
+        }
+        removed
+    }
+
+    pub fn get_value(&self, node: NodeId) -> Option<&Value> {
+        self.values.get(node)
+    }
+
+    /// Grows every time the value of `node` changes; 0 if never written.
+    ///
+    /// Versions are unique across all stores and keep growing across
+    /// `restore`, so a version never refers to two different values of the
+    /// same node, even in a clone of this store.
+    pub fn version(&self, node: NodeId) -> u64 {
+        self.versions.get(node).copied().unwrap_or(0)
+    }
+
+    /// Captures the current values in O(1). Later writes to the store leave
+    /// the snapshot untouched.
+    pub fn snapshot(&self) -> StoreSnapshot {
+        StoreSnapshot {
+            values: self.values.clone(),
+            versions: self.versions.clone(),
+        }
+    }
+
+    /// Returns every node to its value and version in `snapshot`.
+    pub fn restore(&mut self, snapshot: &StoreSnapshot) {
+        self.values = snapshot.values.clone();
+        self.versions = snapshot.versions.clone();
+    }
+
+    fn bump(&mut self, node: NodeId) {
+        let version = CLOCK.fetch_add(1, Ordering::Relaxed) + 1;
+        self.versions.insert(node, version);
+    }
+}
+
+impl StoreSnapshot {
+    pub fn get_value(&self, node: NodeId) -> Option<&Value> {
+        self.values.get(node)
     }
 
-    pub fn get_value(&self, node: NodeId) -> Option<&String> {
-        self.values.get(&node)
+    pub fn version(&self, node: NodeId) -> u64 {
+        self.versions.get(node).copied().unwrap_or(0)
     }
 }
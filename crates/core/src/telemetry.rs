@@ -4,22 +4,31 @@ use std::time::Duration;
 #[cfg(feature = "phase6-telemetry")]
 use std::time::Instant;
 
-/// Represents the outcome of a tick.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents the outcome of a tick. Doubles as the host contract's `metaKind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum TickResult {
+    #[default]
     Commit,
     Rollback,
     Fallback,
 }
 
-impl Default for TickResult {
-    fn default() -> Self {
-        TickResult::Commit
+impl TickResult {
+    /// The `metaKind` string the JS host expects.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TickResult::Commit => "commit",
+            TickResult::Rollback => "rollback",
+            TickResult::Fallback => "fallback",
+        }
     }
 }
 
 /// Duration breakdown for the major phases that telemetry tracks.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PhaseDurations {
     pub script_ms: f64,
     pub style_ms: f64,
@@ -30,19 +39,34 @@ pub struct PhaseDurations {
 
 /// Counters that describe the amount of work a tick emitted.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct WorkBreakdown {
     pub dom_mutations: usize,
     pub nodes_touched: usize,
     pub selectors_evaluated: usize,
     pub elements_invalidated: usize,
     pub patch_bytes: usize,
+    pub patches_skipped: usize,
+    pub selectors_deferred: usize,
+    pub ops_deferred: usize,
+    /// Lookups in the output cache of standalone `Selector::evaluate` calls.
+    /// The engine re-runs selectors without consulting the cache, so its
+    /// ticks leave both at 0.
+    pub selector_cache_hits: usize,
+    pub selector_cache_misses: usize,
 }
 
 /// Guardrail events such as rollbacks or fallbacks, along with the phase they happened in.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GuardrailEvent {
     pub kind: TickResult,
     pub reason: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub phase: Option<String>,
 }
 
@@ -58,12 +82,27 @@ impl GuardrailEvent {
 
 /// Captures a single tick’s telemetry snapshot. This is the data the Phase-6 UI consumes.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TickTelemetry {
     pub tick_id: u64,
     pub result: TickResult,
     pub durations: PhaseDurations,
     pub work: WorkBreakdown,
+    /// Serialized as a decimal string, like `PatchBatch::fingerprint`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::patch::fingerprint_string"
+        )
+    )]
     pub fingerprint: Option<u64>,
+    /// Serialized as `fallback` to match the Phase-6 `TickStats` schema.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "fallback", default, skip_serializing_if = "Option::is_none")
+    )]
     pub guardrail: Option<GuardrailEvent>,
 }
 
@@ -86,6 +125,13 @@ struct ActiveTick {
     guardrail: Option<GuardrailEvent>,
 }
 
+#[cfg(feature = "phase6-telemetry")]
+impl Default for TelemetryRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(feature = "phase6-telemetry")]
 impl TelemetryRecorder {
     pub fn new() -> Self {
@@ -142,12 +188,39 @@ impl TelemetryRecorder {
         }
     }
 
+    pub fn record_skipped_patches(&mut self, count: usize) {
+        if let Some(current) = &mut self.current {
+            current.work.patches_skipped += count;
+        }
+    }
+
+    pub fn record_deferred_work(&mut self, selectors: usize, ops: usize) {
+        if let Some(current) = &mut self.current {
+            current.work.selectors_deferred += selectors;
+            current.work.ops_deferred += ops;
+        }
+    }
+
+    pub fn record_selector_cache(&mut self, hit: bool) {
+        if let Some(current) = &mut self.current {
+            if hit {
+                current.work.selector_cache_hits += 1;
+            } else {
+                current.work.selector_cache_misses += 1;
+            }
+        }
+    }
+
     pub fn record_patch(&mut self, batch: &PatchBatch) {
         if let Some(current) = &mut self.current {
-            let bytes = estimate_patch_bytes(batch);
+            let bytes = estimate_patch_bytes(&batch.ops);
             current.work.dom_mutations = batch.len();
             current.work.patch_bytes = bytes;
-            current.fingerprint = Some(fingerprint_from_batch(batch));
+            current.fingerprint = Some(
+                batch
+                    .fingerprint
+                    .unwrap_or_else(|| crate::patch::fingerprint_ops(&batch.ops)),
+            );
         }
     }
 
@@ -198,36 +271,35 @@ impl TelemetryRecorder {
 
 #[cfg(feature = "phase6-telemetry")]
 fn estimate_patch_bytes(batch: &[crate::patch::PatchOp]) -> usize {
+    use crate::patch::PatchOp;
     const NODE_ID_BYTES: usize = std::mem::size_of::<u64>();
     batch
         .iter()
         .map(|op| match op {
-            crate::patch::PatchOp::SetText { node: _, text } => NODE_ID_BYTES + text.len(),
-            crate::patch::PatchOp::SetAttr {
+            PatchOp::EnsureNode { node: _, tag } => NODE_ID_BYTES + tag.len(),
+            PatchOp::SetText { node: _, text } => NODE_ID_BYTES + text.len(),
+            PatchOp::SetAttr {
                 node: _,
                 name,
                 value,
             } => NODE_ID_BYTES * 2 + name.len() + value.len(),
-            crate::patch::PatchOp::Insert {
+            PatchOp::RemoveAttr { node: _, name } => NODE_ID_BYTES + name.len(),
+            PatchOp::Insert {
                 parent: _,
                 child: _,
             } => NODE_ID_BYTES * 2,
-            crate::patch::PatchOp::Remove { node: _ } => NODE_ID_BYTES,
+            PatchOp::InsertBefore {
+                parent: _,
+                child: _,
+                before: _,
+            } => NODE_ID_BYTES * 3,
+            PatchOp::Remove { node: _ } => NODE_ID_BYTES,
         })
         .sum()
 }
 
-#[cfg(feature = "phase6-telemetry")]
-fn fingerprint_from_batch(batch: &[crate::patch::PatchOp]) -> u64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    let mut hasher = DefaultHasher::new();
-    batch.hash(&mut hasher);
-    hasher.finish()
-}
-
 #[cfg(not(feature = "phase6-telemetry"))]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TelemetryRecorder;
 
 #[cfg(not(feature = "phase6-telemetry"))]
@@ -242,6 +314,9 @@ impl TelemetryRecorder {
     pub fn record_render_duration(&mut self, _duration: Duration) {}
     pub fn record_selector_evaluation(&mut self, _duration: Duration, _elements: usize) {}
     pub fn record_node_touches(&mut self, _count: usize) {}
+    pub fn record_skipped_patches(&mut self, _count: usize) {}
+    pub fn record_deferred_work(&mut self, _selectors: usize, _ops: usize) {}
+    pub fn record_selector_cache(&mut self, _hit: bool) {}
     pub fn record_patch(&mut self, _batch: &PatchBatch) {}
     pub fn record_guardrail(&mut self, _event: GuardrailEvent) {}
     pub fn finalize_tick(&mut self, _result: TickResult) {}
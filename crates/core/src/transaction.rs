@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::path::{Path, PathError};
+use crate::{NodeId, Store, Value};
+
+/// Staged store writes collected by `Engine::update`.
+///
+/// Writes land on copies of the touched nodes and are applied together when
+/// the update closure returns. Each write remembers the path it changed, so
+/// only selectors that read an overlapping path re-run.
+#[derive(Debug)]
+pub struct Transaction<'s> {
+    store: &'s Store,
+    staged: BTreeMap<NodeId, StagedWrite>,
+}
+
+/// A node's new value and the paths that changed; `None` when the whole
+/// value was replaced.
+#[derive(Debug)]
+pub(crate) struct StagedWrite {
+    pub(crate) value: Value,
+    pub(crate) paths: Option<BTreeSet<Path>>,
+}
+
+impl<'s> Transaction<'s> {
+    pub fn new(store: &'s Store) -> Self {
+        Self {
+            store,
+            staged: BTreeMap::new(),
+        }
+    }
+
+    /// The value of `node` including writes staged so far.
+    pub fn get(&self, node: NodeId) -> Option<&Value> {
+        match self.staged.get(&node) {
+            Some(staged) => Some(&staged.value),
+            None => self.store.get_value(node),
+        }
+    }
+
+    pub fn get_path(&self, node: NodeId, path: impl Into<Path>) -> Option<&Value> {
+        self.get(node)?.get_path(&path.into())
+    }
+
+    /// Replaces the whole value of `node`.
+    pub fn set<V: Into<Value>>(&mut self, node: NodeId, value: V) {
+        self.staged.insert(
+            node,
+            StagedWrite {
+                value: value.into(),
+                paths: None,
+            },
+        );
+    }
+
+    /// Writes `value` at `path` inside `node`, e.g. a map field or list item.
+    pub fn set_field<V: Into<Value>>(
+        &mut self,
+        node: NodeId,
+        path: impl Into<Path>,
+        value: V,
+    ) -> Result<(), PathError> {
+        let path = path.into();
+        self.write(node, |current| current.set_path(&path, value.into()))?;
+        self.touch(node, path);
+        Ok(())
+    }
+
+    /// Appends `value` to the list at `path` inside `node`.
+    pub fn push<V: Into<Value>>(
+        &mut self,
+        node: NodeId,
+        path: impl Into<Path>,
+        value: V,
+    ) -> Result<(), PathError> {
+        let path = path.into();
+        let index = self.write(node, |current| current.push_path(&path, value.into()))?;
+        self.touch(node, path.index(index));
+        Ok(())
+    }
+
+    /// Replaces `delete` items of the list at `path` starting at `start` with
+    /// `items`, returning the removed items.
+    ///
+    /// Later items shift, so the whole list counts as changed.
+    pub fn splice(
+        &mut self,
+        node: NodeId,
+        path: impl Into<Path>,
+        start: usize,
+        delete: usize,
+        items: Vec<Value>,
+    ) -> Result<Vec<Value>, PathError> {
+        let path = path.into();
+        let removed = self.write(node, |current| {
+            current.splice_path(&path, start, delete, items)
+        })?;
+        self.touch(node, path);
+        Ok(removed)
+    }
+
+    pub(crate) fn into_writes(self) -> BTreeMap<NodeId, StagedWrite> {
+        self.staged
+    }
+
+    /// Applies `edit` to the staged copy of the node's value. Edits that
+    /// fail leave the value as it was, and stage nothing for a node they
+    /// would have been the first write to.
+    fn write<T>(
+        &mut self,
+        node: NodeId,
+        edit: impl FnOnce(&mut Value) -> Result<T, PathError>,
+    ) -> Result<T, PathError> {
+        if let Some(staged) = self.staged.get_mut(&node) {
+            return edit(&mut staged.value);
+        }
+        let mut value = self.store.get_value(node).cloned().unwrap_or_default();
+        let result = edit(&mut value)?;
+        self.staged.insert(
+            node,
+            StagedWrite {
+                value,
+                paths: Some(BTreeSet::new()),
+            },
+        );
+        Ok(result)
+    }
+
+    fn touch(&mut self, node: NodeId, path: Path) {
+        if let Some(paths) = self
+            .staged
+            .get_mut(&node)
+            .and_then(|staged| staged.paths.as_mut())
+        {
+            paths.insert(path);
+        }
+    }
+}
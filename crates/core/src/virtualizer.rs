@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::keyed::{diff_keyed_children, KeyedChild};
+use crate::patch::PatchOp;
+use crate::NodeId;
+
+/// One row in a virtualized list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualRow {
+    pub key: String,
+    pub node: NodeId,
+    pub text: String,
+}
+
+impl VirtualRow {
+    pub fn new(key: impl Into<String>, node: NodeId, text: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            node,
+            text: text.into(),
+        }
+    }
+}
+
+/// Keeps only the rows around the viewport mounted under `container`.
+///
+/// Rows are laid out top to bottom using the estimated row size unless a
+/// measured size was reported for their key. `update` returns the ops that
+/// move the mounted window to the current viewport: rows entering it are
+/// created with `EnsureNode` (using the row tag, `div` unless set), get
+/// `SetText` and are inserted in order, and rows that stay mounted only get
+/// `SetText` when their text changed.
+///
+/// Rows leaving the window get `Remove`, which the host treats as destroying
+/// the node, so a row scrolled back into view is created again. Rows must
+/// therefore not own host state beyond their text.
+#[derive(Debug, Clone)]
+pub struct Virtualizer {
+    container: NodeId,
+    rows: Vec<VirtualRow>,
+    estimated_size: f64,
+    measured: HashMap<String, f64>,
+    offset: f64,
+    height: f64,
+    overscan: usize,
+    row_tag: String,
+    starts: Vec<f64>,
+    layout_dirty: bool,
+    mounted: Vec<VirtualRow>,
+}
+
+impl Virtualizer {
+    pub fn new(container: NodeId, estimated_size: f64) -> Self {
+        Self {
+            container,
+            rows: Vec::new(),
+            estimated_size,
+            measured: HashMap::new(),
+            offset: 0.0,
+            height: 0.0,
+            overscan: 0,
+            row_tag: "div".to_string(),
+            starts: vec![0.0],
+            layout_dirty: false,
+            mounted: Vec::new(),
+        }
+    }
+
+    pub fn with_viewport(mut self, offset: f64, height: f64) -> Self {
+        self.offset = offset;
+        self.height = height;
+        self
+    }
+
+    pub fn with_overscan(mut self, overscan: usize) -> Self {
+        self.overscan = overscan;
+        self
+    }
+
+    pub fn with_row_tag(mut self, tag: impl Into<String>) -> Self {
+        self.row_tag = tag.into();
+        self
+    }
+
+    pub fn container(&self) -> NodeId {
+        self.container
+    }
+
+    pub fn rows(&self) -> &[VirtualRow] {
+        &self.rows
+    }
+
+    pub fn set_rows(&mut self, rows: Vec<VirtualRow>) {
+        self.rows = rows;
+        self.layout_dirty = true;
+    }
+
+    pub fn scroll_to(&mut self, offset: f64) {
+        self.offset = offset;
+    }
+
+    pub fn resize(&mut self, height: f64) {
+        self.height = height;
+    }
+
+    /// Records the rendered size of the row with `key`, overriding the estimate.
+    pub fn measure(&mut self, key: impl Into<String>, size: f64) {
+        self.measured.insert(key.into(), size);
+        self.layout_dirty = true;
+    }
+
+    /// Sum of every row's size, for sizing the scroll container.
+    pub fn total_size(&mut self) -> f64 {
+        self.layout();
+        self.starts[self.rows.len()]
+    }
+
+    /// Distance from the top of the list to the start of row `index`.
+    pub fn offset_of(&mut self, index: usize) -> f64 {
+        self.layout();
+        self.starts[index.min(self.rows.len())]
+    }
+
+    /// Indices of the rows intersecting the viewport, widened by the overscan.
+    pub fn visible_range(&mut self) -> Range<usize> {
+        self.layout();
+        let len = self.rows.len();
+        let top = self.offset.max(0.0);
+        let bottom = top + self.height.max(0.0);
+        // `starts` has one more entry than `rows`; a row is visible when it
+        // ends after `top` and starts before `bottom`.
+        let first = self.starts[1..].partition_point(|&end| end <= top);
+        let last = self.starts[..len].partition_point(|&start| start < bottom);
+        let first = first.min(last);
+        first.saturating_sub(self.overscan)..last.saturating_add(self.overscan).min(len)
+    }
+
+    pub fn mounted(&self) -> &[VirtualRow] {
+        &self.mounted
+    }
+
+    pub fn update(&mut self) -> Vec<PatchOp> {
+        let range = self.visible_range();
+        let next = self.rows[range].to_vec();
+
+        let previous: HashMap<&str, &VirtualRow> = self
+            .mounted
+            .iter()
+            .map(|row| (row.key.as_str(), row))
+            .collect();
+        let old_children: Vec<_> = self.mounted.iter().map(keyed_child).collect();
+        let new_children: Vec<_> = next.iter().map(keyed_child).collect();
+
+        let mut ops = diff_keyed_children(self.container, &old_children, &new_children);
+        let mut created = Vec::new();
+        for row in &next {
+            let set_text = PatchOp::SetText {
+                node: row.node,
+                text: row.text.clone(),
+            };
+            match previous.get(row.key.as_str()) {
+                Some(old) if old.node == row.node => {
+                    if old.text != row.text {
+                        ops.push(set_text);
+                    }
+                }
+                _ => {
+                    created.push(PatchOp::EnsureNode {
+                        node: row.node,
+                        tag: self.row_tag.clone(),
+                    });
+                    created.push(set_text);
+                }
+            }
+        }
+        // Create entering rows after the removals, in case a node id was
+        // reused, and before the inserts that place them.
+        let first_placement = ops
+            .iter()
+            .position(|op| !matches!(op, PatchOp::Remove { .. }))
+            .unwrap_or(ops.len());
+        ops.splice(first_placement..first_placement, created);
+
+        self.mounted = next;
+        ops
+    }
+
+    fn layout(&mut self) {
+        if !self.layout_dirty {
+            return;
+        }
+        self.layout_dirty = false;
+        self.starts.clear();
+        self.starts.reserve(self.rows.len() + 1);
+        let mut cursor = 0.0;
+        self.starts.push(cursor);
+        for row in &self.rows {
+            cursor += self
+                .measured
+                .get(&row.key)
+                .copied()
+                .unwrap_or(self.estimated_size);
+            self.starts.push(cursor);
+        }
+    }
+}
+
+fn keyed_child(row: &VirtualRow) -> KeyedChild<&str> {
+    KeyedChild::new(row.key.as_str(), row.node)
+}
@@ -0,0 +1,349 @@
+//! Compact binary encoding for `PatchBatch`, so one buffer crosses the
+//! JS↔WASM boundary per tick.
+//!
+//! Layout (version 1), all integers unsigned LEB128 varints unless noted:
+//!
+//! ```text
+//! magic "JCPB" | version u8 | meta kind u8 | flags u8
+//! [batch_id] [tick_id] [fingerprint u64 LE] [reason string index]
+//! string count | (byte length | utf-8 bytes)*
+//! op count | (opcode u8 | operands)*
+//! ```
+//!
+//! `flags` marks which optional fields follow (bit 0 batch id, bit 1 tick id,
+//! bit 2 fingerprint, bit 3 reason). Every string (tags, text, attribute names
+//! and values, the reason) is interned once in the string table and referenced
+//! by index; node ids are written as varints.
+
+use std::collections::HashMap;
+
+use crate::patch::{PatchBatch, PatchOp};
+use crate::telemetry::TickResult;
+use crate::NodeId;
+
+pub const WIRE_MAGIC: [u8; 4] = *b"JCPB";
+pub const WIRE_VERSION: u8 = 1;
+
+const FLAG_BATCH_ID: u8 = 1 << 0;
+const FLAG_TICK_ID: u8 = 1 << 1;
+const FLAG_FINGERPRINT: u8 = 1 << 2;
+const FLAG_REASON: u8 = 1 << 3;
+
+const OP_ENSURE_NODE: u8 = 1;
+const OP_SET_TEXT: u8 = 2;
+const OP_SET_ATTR: u8 = 3;
+const OP_REMOVE_ATTR: u8 = 4;
+const OP_APPEND_CHILD: u8 = 5;
+const OP_INSERT_BEFORE: u8 = 6;
+const OP_REMOVE: u8 = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEof,
+    VarintOverflow,
+    InvalidUtf8,
+    UnknownMetaKind(u8),
+    UnknownOpcode(u8),
+    StringIndexOutOfRange(u64),
+    TrailingBytes,
+}
+
+pub fn encode_batch(batch: &PatchBatch) -> Vec<u8> {
+    let mut strings = StringTable::default();
+    let body = encode_op_section(&batch.ops, &mut strings);
+    let reason = batch.reason.as_deref().map(|reason| strings.intern(reason));
+
+    let mut flags = 0;
+    if batch.batch_id.is_some() {
+        flags |= FLAG_BATCH_ID;
+    }
+    if batch.tick_id.is_some() {
+        flags |= FLAG_TICK_ID;
+    }
+    if batch.fingerprint.is_some() {
+        flags |= FLAG_FINGERPRINT;
+    }
+    if reason.is_some() {
+        flags |= FLAG_REASON;
+    }
+
+    let mut out = Vec::with_capacity(body.len() + strings.byte_len() + 32);
+    out.extend_from_slice(&WIRE_MAGIC);
+    out.push(WIRE_VERSION);
+    out.push(meta_kind_code(batch.meta_kind));
+    out.push(flags);
+    if let Some(batch_id) = batch.batch_id {
+        write_varint(&mut out, batch_id);
+    }
+    if let Some(tick_id) = batch.tick_id {
+        write_varint(&mut out, tick_id);
+    }
+    if let Some(fingerprint) = batch.fingerprint {
+        out.extend_from_slice(&fingerprint.to_le_bytes());
+    }
+    if let Some(reason) = reason {
+        write_varint(&mut out, reason);
+    }
+    strings.write(&mut out);
+    out.extend_from_slice(&body);
+    out
+}
+
+/// The string table and op section `encode_batch` writes for a batch holding
+/// `ops` and no reason.
+pub(crate) fn encode_ops(ops: &[PatchOp]) -> Vec<u8> {
+    let mut strings = StringTable::default();
+    let body = encode_op_section(ops, &mut strings);
+    let mut out = Vec::with_capacity(body.len() + strings.byte_len() + 8);
+    strings.write(&mut out);
+    out.extend_from_slice(&body);
+    out
+}
+
+fn encode_op_section<'a>(ops: &'a [PatchOp], strings: &mut StringTable<'a>) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_varint(&mut body, ops.len() as u64);
+    for op in ops {
+        match op {
+            PatchOp::EnsureNode { node, tag } => {
+                body.push(OP_ENSURE_NODE);
+                write_node(&mut body, *node);
+                write_varint(&mut body, strings.intern(tag));
+            }
+            PatchOp::SetText { node, text } => {
+                body.push(OP_SET_TEXT);
+                write_node(&mut body, *node);
+                write_varint(&mut body, strings.intern(text));
+            }
+            PatchOp::SetAttr { node, name, value } => {
+                body.push(OP_SET_ATTR);
+                write_node(&mut body, *node);
+                write_varint(&mut body, strings.intern(name));
+                write_varint(&mut body, strings.intern(value));
+            }
+            PatchOp::RemoveAttr { node, name } => {
+                body.push(OP_REMOVE_ATTR);
+                write_node(&mut body, *node);
+                write_varint(&mut body, strings.intern(name));
+            }
+            PatchOp::Insert { parent, child } => {
+                body.push(OP_APPEND_CHILD);
+                write_node(&mut body, *parent);
+                write_node(&mut body, *child);
+            }
+            PatchOp::InsertBefore {
+                parent,
+                child,
+                before,
+            } => {
+                body.push(OP_INSERT_BEFORE);
+                write_node(&mut body, *parent);
+                write_node(&mut body, *child);
+                write_node(&mut body, *before);
+            }
+            PatchOp::Remove { node } => {
+                body.push(OP_REMOVE);
+                write_node(&mut body, *node);
+            }
+        }
+    }
+    body
+}
+
+pub fn decode_batch(bytes: &[u8]) -> Result<PatchBatch, WireError> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(WIRE_MAGIC.len())? != WIRE_MAGIC {
+        return Err(WireError::BadMagic);
+    }
+    let version = reader.byte()?;
+    if version != WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(version));
+    }
+    let meta_kind = match reader.byte()? {
+        0 => TickResult::Commit,
+        1 => TickResult::Rollback,
+        2 => TickResult::Fallback,
+        other => return Err(WireError::UnknownMetaKind(other)),
+    };
+    let flags = reader.byte()?;
+    let batch_id = (flags & FLAG_BATCH_ID != 0)
+        .then(|| reader.varint())
+        .transpose()?;
+    let tick_id = (flags & FLAG_TICK_ID != 0)
+        .then(|| reader.varint())
+        .transpose()?;
+    let fingerprint = (flags & FLAG_FINGERPRINT != 0)
+        .then(|| reader.u64_le())
+        .transpose()?;
+    let reason_index = (flags & FLAG_REASON != 0)
+        .then(|| reader.varint())
+        .transpose()?;
+
+    let string_count = reader.varint()?;
+    let mut strings = Vec::new();
+    for _ in 0..string_count {
+        let len = reader.varint()?;
+        let len = usize::try_from(len).map_err(|_| WireError::UnexpectedEof)?;
+        let raw = reader.take(len)?;
+        let text = std::str::from_utf8(raw).map_err(|_| WireError::InvalidUtf8)?;
+        strings.push(text.to_string());
+    }
+    let string = |index: u64| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| strings.get(index))
+            .cloned()
+            .ok_or(WireError::StringIndexOutOfRange(index))
+    };
+
+    let op_count = reader.varint()?;
+    let mut ops = Vec::new();
+    for _ in 0..op_count {
+        let op = match reader.byte()? {
+            OP_ENSURE_NODE => PatchOp::EnsureNode {
+                node: reader.node()?,
+                tag: string(reader.varint()?)?,
+            },
+            OP_SET_TEXT => PatchOp::SetText {
+                node: reader.node()?,
+                text: string(reader.varint()?)?,
+            },
+            OP_SET_ATTR => PatchOp::SetAttr {
+                node: reader.node()?,
+                name: string(reader.varint()?)?,
+                value: string(reader.varint()?)?,
+            },
+            OP_REMOVE_ATTR => PatchOp::RemoveAttr {
+                node: reader.node()?,
+                name: string(reader.varint()?)?,
+            },
+            OP_APPEND_CHILD => PatchOp::Insert {
+                parent: reader.node()?,
+                child: reader.node()?,
+            },
+            OP_INSERT_BEFORE => PatchOp::InsertBefore {
+                parent: reader.node()?,
+                child: reader.node()?,
+                before: reader.node()?,
+            },
+            OP_REMOVE => PatchOp::Remove {
+                node: reader.node()?,
+            },
+            other => return Err(WireError::UnknownOpcode(other)),
+        };
+        ops.push(op);
+    }
+
+    if reader.pos != bytes.len() {
+        return Err(WireError::TrailingBytes);
+    }
+
+    Ok(PatchBatch {
+        meta_kind,
+        ops,
+        batch_id,
+        tick_id,
+        reason: reason_index.map(string).transpose()?,
+        fingerprint,
+    })
+}
+
+fn meta_kind_code(kind: TickResult) -> u8 {
+    match kind {
+        TickResult::Commit => 0,
+        TickResult::Rollback => 1,
+        TickResult::Fallback => 2,
+    }
+}
+
+#[derive(Default)]
+struct StringTable<'a> {
+    indices: HashMap<&'a str, u64>,
+    entries: Vec<&'a str>,
+}
+
+impl<'a> StringTable<'a> {
+    fn intern(&mut self, value: &'a str) -> u64 {
+        if let Some(&index) = self.indices.get(value) {
+            return index;
+        }
+        let index = self.entries.len() as u64;
+        self.entries.push(value);
+        self.indices.insert(value, index);
+        index
+    }
+
+    fn byte_len(&self) -> usize {
+        self.entries.iter().map(|entry| entry.len() + 2).sum()
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.entries.len() as u64);
+        for entry in &self.entries {
+            write_varint(out, entry.len() as u64);
+            out.extend_from_slice(entry.as_bytes());
+        }
+    }
+}
+
+fn write_node(out: &mut Vec<u8>, node: NodeId) {
+    write_varint(out, node.raw());
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        let end = self.pos.checked_add(len).ok_or(WireError::UnexpectedEof)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(WireError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, WireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64_le(&mut self) -> Result<u64, WireError> {
+        let mut raw = [0; 8];
+        raw.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(raw))
+    }
+
+    fn varint(&mut self) -> Result<u64, WireError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let chunk = u64::from(byte & 0x7f);
+            if shift == 63 && chunk > 1 {
+                return Err(WireError::VarintOverflow);
+            }
+            value |= chunk << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(WireError::VarintOverflow)
+    }
+
+    fn node(&mut self) -> Result<NodeId, WireError> {
+        self.varint().map(NodeId::new)
+    }
+}
@@ -1,4 +1,10 @@
-use crust_core::{Engine, NodeId, PatchOp, SchedulerError};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crust_core::{
+    Binding, BoxedSelector, Engine, Lane, NodeId, PatchOp, Path, PathError, SchedulerError,
+    SelectorError, TickBudget, TickResult, Value,
+};
 
 #[test]
 fn engine_emits_patch_batch_per_tick() {
@@ -11,7 +17,7 @@ fn engine_emits_patch_batch_per_tick() {
     let batch = engine.commit().unwrap();
 
     assert_eq!(
-        batch,
+        batch.ops,
         vec![
             PatchOp::SetText {
                 node: NodeId::new(1),
@@ -33,3 +39,1131 @@ fn engine_rejects_commit_without_tick() {
 
     assert!(matches!(result, Err(SchedulerError::TickNotStarted)));
 }
+
+#[test]
+fn engine_recomputes_dependent_selectors_in_order() {
+    let mut engine = Engine::new();
+    let name = NodeId::new(1);
+    let greeting = NodeId::new(10);
+    let banner = NodeId::new(5);
+
+    engine.register_selector(banner, move |ctx| {
+        ctx.read(greeting)
+            .unwrap_or_default()
+            .to_text()
+            .to_uppercase()
+    });
+    engine.register_selector(greeting, move |ctx| {
+        format!("hello, {}", ctx.read(name).unwrap_or_default())
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(name, "ada").unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(name, "bob").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops,
+        vec![
+            PatchOp::SetText {
+                node: name,
+                text: "bob".to_string(),
+            },
+            PatchOp::SetText {
+                node: greeting,
+                text: "hello, bob".to_string(),
+            },
+            PatchOp::SetText {
+                node: banner,
+                text: "HELLO, BOB".to_string(),
+            },
+        ]
+    );
+    assert_eq!(engine.graph().dependents_of(greeting), vec![banner]);
+}
+
+#[test]
+fn engine_skips_selectors_outside_dirty_set() {
+    let mut engine = Engine::new();
+    let left = NodeId::new(1);
+    let right = NodeId::new(2);
+
+    engine.register_selector(NodeId::new(11), move |ctx| {
+        ctx.read(left).unwrap_or_default()
+    });
+    engine.register_selector(NodeId::new(12), move |ctx| {
+        ctx.read(right).unwrap_or_default()
+    });
+
+    engine.begin_tick().unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(left, "only-left").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops,
+        vec![
+            PatchOp::SetText {
+                node: left,
+                text: "only-left".to_string(),
+            },
+            PatchOp::SetText {
+                node: NodeId::new(11),
+                text: "only-left".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn engine_unregister_selector_stops_recompute() {
+    let mut engine = Engine::new();
+    let source = NodeId::new(1);
+    let selector = NodeId::new(2);
+
+    engine.register_selector(selector, move |ctx| ctx.read(source).unwrap_or_default());
+    engine.begin_tick().unwrap();
+    engine.set_value(source, "a").unwrap();
+    engine.commit().unwrap();
+
+    assert!(engine.unregister_selector(selector));
+    assert!(engine.graph().dependents_of(source).is_empty());
+
+    engine.begin_tick().unwrap();
+    engine.set_value(source, "b").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops,
+        vec![PatchOp::SetText {
+            node: source,
+            text: "b".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn engine_keeps_values_typed_until_patch_boundary() {
+    let mut engine = Engine::new();
+    let items = NodeId::new(1);
+    let count = NodeId::new(2);
+
+    engine.register_selector(count, move |ctx| {
+        ctx.read(items)
+            .as_ref()
+            .and_then(Value::as_list)
+            .map_or(0, |list| list.len() as i64)
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(items, vec![1, 2, 3]).unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(engine.store().get_value(count), Some(&Value::Int(3)));
+    assert_eq!(
+        batch.ops,
+        vec![
+            PatchOp::SetText {
+                node: items,
+                text: "[1,2,3]".to_string(),
+            },
+            PatchOp::SetText {
+                node: count,
+                text: "3".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn engine_skips_patches_for_unchanged_values() {
+    let mut engine = Engine::new();
+    let node = NodeId::new(1);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(node, "same").unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(node, "same").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert!(batch.is_empty());
+}
+
+#[test]
+fn engine_cuts_off_propagation_when_selector_output_is_unchanged() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let parity = NodeId::new(2);
+    let label = NodeId::new(3);
+    let label_runs = Rc::new(Cell::new(0));
+
+    engine.register_selector(parity, move |ctx| {
+        let value = ctx
+            .read(input)
+            .and_then(|value| value.as_int())
+            .unwrap_or(0);
+        if value % 2 == 0 {
+            "even"
+        } else {
+            "odd"
+        }
+    });
+    let runs = Rc::clone(&label_runs);
+    engine.register_selector(label, move |ctx| {
+        runs.set(runs.get() + 1);
+        format!("parity: {}", ctx.read(parity).unwrap_or_default())
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, 2).unwrap();
+    engine.commit().unwrap();
+    let runs_after_first_tick = label_runs.get();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, 4).unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops,
+        vec![PatchOp::SetText {
+            node: input,
+            text: "4".to_string(),
+        }]
+    );
+    assert_eq!(label_runs.get(), runs_after_first_tick);
+}
+
+#[test]
+fn engine_defers_background_selectors_over_budget() {
+    let mut engine = Engine::new();
+    let query = NodeId::new(1);
+    let feed = NodeId::new(2);
+
+    engine.register_selector_in(Lane::Background, feed, move |ctx| {
+        format!("results for {}", ctx.read(query).unwrap_or_default())
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(query, "rust").unwrap();
+    let urgent = engine.commit_with_budget(TickBudget::ops(1)).unwrap();
+    assert_eq!(
+        urgent.ops,
+        vec![PatchOp::SetText {
+            node: query,
+            text: "rust".to_string(),
+        }]
+    );
+
+    engine.begin_tick().unwrap();
+    let carried = engine.commit().unwrap();
+    assert_eq!(
+        carried.ops,
+        vec![PatchOp::SetText {
+            node: feed,
+            text: "results for rust".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn engine_resumes_recomputation_deferred_by_budget() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let first = NodeId::new(10);
+    let second = NodeId::new(11);
+    let third = NodeId::new(12);
+
+    engine.register_selector(first, move |ctx| {
+        format!("{}!", ctx.read(input).unwrap_or_default())
+    });
+    engine.register_selector(second, move |ctx| {
+        format!("{}!", ctx.read(first).unwrap_or_default())
+    });
+    engine.register_selector(third, move |ctx| {
+        format!("{}!", ctx.read(second).unwrap_or_default())
+    });
+    engine.begin_tick().unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "x").unwrap();
+    let partial = engine.commit_with_budget(TickBudget::ops(2)).unwrap();
+
+    assert_eq!(
+        partial.ops,
+        vec![
+            PatchOp::SetText {
+                node: input,
+                text: "x".to_string(),
+            },
+            PatchOp::SetText {
+                node: first,
+                text: "x!".to_string(),
+            },
+        ]
+    );
+    assert!(engine.has_pending_work());
+
+    engine.begin_tick().unwrap();
+    let rest = engine.commit().unwrap();
+
+    assert_eq!(
+        rest.ops,
+        vec![
+            PatchOp::SetText {
+                node: second,
+                text: "x!!".to_string(),
+            },
+            PatchOp::SetText {
+                node: third,
+                text: "x!!!".to_string(),
+            },
+        ]
+    );
+    assert!(!engine.has_pending_work());
+}
+
+#[test]
+fn engine_budget_ignores_work_carried_from_earlier_ticks() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let echo = NodeId::new(10);
+    engine.register_selector(echo, move |ctx| ctx.read(input).unwrap_or_default());
+
+    engine.begin_tick().unwrap();
+    for raw in 20..23 {
+        engine
+            .enqueue_op_in(
+                Lane::Background,
+                PatchOp::Remove {
+                    node: NodeId::new(raw),
+                },
+            )
+            .unwrap();
+    }
+    engine.commit_with_budget(TickBudget::ops(0)).unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "typed").unwrap();
+    let batch = engine.commit_with_budget(TickBudget::ops(2)).unwrap();
+    assert!(batch.ops.contains(&PatchOp::SetText {
+        node: echo,
+        text: "typed".to_string(),
+    }));
+
+    let mut ticks = 0;
+    while engine.has_pending_work() {
+        engine.begin_tick().unwrap();
+        engine.commit_with_budget(TickBudget::ops(2)).unwrap();
+        ticks += 1;
+        assert!(ticks <= 2, "carried ops never drained");
+    }
+}
+
+#[test]
+fn engine_commit_batches_carry_metadata() {
+    let mut engine = Engine::new();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(NodeId::new(1), "meta").unwrap();
+    let first = engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    let second = engine.commit().unwrap();
+
+    assert_eq!(first.meta_kind, TickResult::Commit);
+    assert_eq!(first.meta_kind.as_str(), "commit");
+    assert_eq!(first.tick_id, Some(1));
+    assert_eq!(second.tick_id, Some(2));
+    assert_ne!(first.batch_id, second.batch_id);
+    assert!(first.fingerprint.is_some());
+    assert_ne!(first.fingerprint, second.fingerprint);
+    assert_eq!(first.reason, None);
+}
+
+#[test]
+fn engine_rollback_and_fallback_emit_empty_batches() {
+    let mut engine = Engine::new();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(NodeId::new(1), "discarded").unwrap();
+    let rollback = engine.rollback("forbidden op").unwrap();
+
+    assert_eq!(rollback.meta_kind, TickResult::Rollback);
+    assert!(rollback.ops.is_empty());
+    assert_eq!(rollback.reason.as_deref(), Some("forbidden op"));
+    assert_eq!(rollback.tick_id, Some(1));
+
+    engine.begin_tick().unwrap();
+    let fallback = engine.fallback("unsupported layout").unwrap();
+    assert_eq!(fallback.meta_kind, TickResult::Fallback);
+    assert!(fallback.is_empty());
+
+    assert!(matches!(
+        engine.rollback("no tick"),
+        Err(SchedulerError::TickNotStarted)
+    ));
+}
+
+#[test]
+fn engine_rollback_tick_restores_store() {
+    let mut engine = Engine::new();
+    let kept = NodeId::new(1);
+    let fresh = NodeId::new(2);
+    let echo = NodeId::new(3);
+    engine.register_selector(echo, move |ctx| ctx.read(kept).unwrap_or_default());
+
+    engine.begin_tick().unwrap();
+    engine.set_value(kept, "committed").unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(kept, "draft").unwrap();
+    engine.set_value(kept, "draft 2").unwrap();
+    engine.set_value(fresh, "new").unwrap();
+    engine.rollback_tick("abandoned").unwrap();
+
+    assert_eq!(
+        engine.store().get_value(kept),
+        Some(&Value::from("committed"))
+    );
+    assert_eq!(engine.store().get_value(fresh), None);
+    assert!(!engine.has_pending_work());
+
+    engine.begin_tick().unwrap();
+    let batch = engine.commit().unwrap();
+    assert!(batch.is_empty());
+    assert_eq!(
+        engine.store().get_value(echo),
+        Some(&Value::from("committed"))
+    );
+}
+
+#[test]
+fn engine_falls_back_on_selector_cycle() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let left = NodeId::new(2);
+    let right = NodeId::new(3);
+    engine.register_selector(left, move |ctx| {
+        let seed = ctx.read(input).unwrap_or_default();
+        format!("{seed}{}", ctx.read(right).unwrap_or_default())
+    });
+    engine.register_selector(right, move |ctx| ctx.read(left).unwrap_or_default());
+
+    for seed in ["a", "b"] {
+        engine.begin_tick().unwrap();
+        engine.set_value(input, seed).unwrap();
+        let batch = engine.commit().unwrap();
+
+        assert_eq!(batch.meta_kind, TickResult::Fallback);
+        assert!(batch.is_empty());
+        assert_eq!(batch.reason.as_deref(), Some("selector cycle: 2 -> 3 -> 2"));
+        assert_eq!(engine.store().get_value(input), None);
+        assert_eq!(engine.store().get_value(left), None);
+    }
+}
+
+#[test]
+fn engine_falls_back_on_cycles_formed_by_on_demand_reads() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let left = NodeId::new(2);
+    let right = NodeId::new(3);
+    engine.register_selector(left, move |ctx| {
+        let seed = ctx.read(input).unwrap_or_default();
+        if seed == "loop" {
+            ctx.read(right).unwrap_or_default()
+        } else {
+            seed
+        }
+    });
+    engine.register_selector(right, move |ctx| {
+        let seed = ctx.read(input).unwrap_or_default();
+        format!("{seed}<{}>", ctx.read(left).unwrap_or_default())
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "a").unwrap();
+    engine.commit().unwrap();
+    assert_eq!(engine.store().get_value(right), Some(&Value::from("a<a>")));
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "loop").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(batch.meta_kind, TickResult::Fallback);
+    assert_eq!(batch.reason.as_deref(), Some("selector cycle: 2 -> 3 -> 2"));
+    assert_eq!(engine.store().get_value(input), Some(&Value::from("a")));
+    assert_eq!(engine.store().get_value(left), Some(&Value::from("a")));
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "b").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(batch.meta_kind, TickResult::Commit);
+    assert_eq!(engine.store().get_value(right), Some(&Value::from("b<b>")));
+}
+
+#[test]
+fn engine_computes_derived_selectors_on_demand() {
+    let mut engine = Engine::new();
+    let items = NodeId::new(1);
+    let visible = NodeId::new(10);
+    let filtered = NodeId::new(20);
+    let runs = Rc::new(Cell::new(0));
+    let visible_runs = Rc::new(Cell::new(0));
+
+    // `visible` has the lower id, so it is reached first before any edge exists.
+    let counter = visible_runs.clone();
+    engine.register_selector(visible, move |ctx| {
+        counter.set(counter.get() + 1);
+        let filtered = ctx.read(filtered).unwrap_or_default();
+        let list = filtered.as_list().unwrap_or_default();
+        Value::from(list.iter().take(2).cloned().collect::<Vec<_>>())
+    });
+    let counter = runs.clone();
+    engine.register_selector(filtered, move |ctx| {
+        counter.set(counter.get() + 1);
+        let items = ctx.read(items).unwrap_or_default();
+        let list = items.as_list().unwrap_or_default();
+        let even: Vec<Value> = list
+            .iter()
+            .filter(|item| item.as_int().is_some_and(|n| n % 2 == 0))
+            .cloned()
+            .collect();
+        Value::from(even)
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(items, vec![1, 2, 3, 4, 5, 6]).unwrap();
+    let batch = engine.commit().unwrap();
+
+    // `filtered` ran once, on demand, and `visible` saw its fresh output.
+    assert_eq!(
+        batch.ops,
+        vec![
+            PatchOp::SetText {
+                node: items,
+                text: "[1,2,3,4,5,6]".to_string(),
+            },
+            PatchOp::SetText {
+                node: visible,
+                text: "[2,4]".to_string(),
+            },
+            PatchOp::SetText {
+                node: filtered,
+                text: "[2,4,6]".to_string(),
+            },
+        ]
+    );
+    assert_eq!((runs.get(), visible_runs.get()), (1, 1));
+    assert_eq!(engine.graph().dependencies_of(visible), vec![filtered]);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(items, vec![8, 9, 10]).unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops,
+        vec![
+            PatchOp::SetText {
+                node: items,
+                text: "[8,9,10]".to_string(),
+            },
+            PatchOp::SetText {
+                node: filtered,
+                text: "[8,10]".to_string(),
+            },
+            PatchOp::SetText {
+                node: visible,
+                text: "[8,10]".to_string(),
+            },
+        ]
+    );
+    assert_eq!((runs.get(), visible_runs.get()), (2, 2));
+}
+
+#[test]
+fn engine_writes_bound_selector_outputs_to_their_targets() {
+    let mut engine = Engine::new();
+    let count = NodeId::new(1);
+    let label = NodeId::new(50);
+    let badge = NodeId::new(51);
+
+    let selectors = vec![
+        (
+            BoxedSelector::boxed(NodeId::new(100), move |ctx| {
+                format!("{} items", ctx.read(count).unwrap_or_default())
+            }),
+            Binding::text(label),
+        ),
+        (
+            BoxedSelector::boxed(NodeId::new(101), move |ctx| {
+                match ctx.read(count).and_then(|value| value.as_int()) {
+                    Some(0) | None => Value::Null,
+                    Some(_) => Value::from("has-items"),
+                }
+            }),
+            Binding::attr(badge, "class"),
+        ),
+    ];
+    for (selector, binding) in selectors {
+        engine.register_bound_selector(selector, binding);
+    }
+
+    engine.begin_tick().unwrap();
+    engine.set_value(count, 3).unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops[1..],
+        [
+            PatchOp::SetText {
+                node: label,
+                text: "3 items".to_string(),
+            },
+            PatchOp::SetAttr {
+                node: badge,
+                name: "class".to_string(),
+                value: "has-items".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        engine.store().get_value(NodeId::new(100)),
+        Some(&Value::from("3 items"))
+    );
+
+    engine.begin_tick().unwrap();
+    engine.set_value(count, 0).unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops[2],
+        PatchOp::RemoveAttr {
+            node: badge,
+            name: "class".to_string(),
+        }
+    );
+    assert_eq!(
+        engine.binding_of(NodeId::new(101)),
+        Some(&Binding::attr(badge, "class"))
+    );
+}
+
+#[test]
+fn engine_rebinding_a_selector_publishes_to_the_new_target() {
+    let mut engine = Engine::new();
+    let title = NodeId::new(1);
+    let id = NodeId::new(10);
+    let selector = move || BoxedSelector::boxed(id, move |ctx| ctx.read(title).unwrap_or_default());
+
+    engine.register_bound_selector(selector(), Binding::text(NodeId::new(2)));
+    engine.begin_tick().unwrap();
+    engine.set_value(title, "hello").unwrap();
+    engine.commit().unwrap();
+
+    engine.register_bound_selector(selector(), Binding::attr(NodeId::new(3), "title"));
+    engine.begin_tick().unwrap();
+    let batch = engine.commit().unwrap();
+    assert_eq!(
+        batch.ops,
+        vec![PatchOp::SetAttr {
+            node: NodeId::new(3),
+            name: "title".to_string(),
+            value: "hello".to_string(),
+        }]
+    );
+
+    engine.begin_tick().unwrap();
+    assert!(engine.commit().unwrap().is_empty());
+
+    assert!(engine.unregister_selector(id));
+    assert_eq!(engine.store().get_value(id), None);
+}
+
+#[test]
+fn engine_falls_back_when_a_selector_fails() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let echo = NodeId::new(2);
+    let checked = NodeId::new(3);
+    engine.register_selector(echo, move |ctx| {
+        format!("{}!", ctx.read(input).unwrap_or_default())
+    });
+    engine.register_selector(checked, move |ctx| {
+        let value = ctx.read(input).unwrap_or_default();
+        if value == "bad" {
+            return Err(SelectorError::failed("rejected input"));
+        }
+        Ok(value)
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "good").unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "bad").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(batch.meta_kind, TickResult::Fallback);
+    assert!(batch.is_empty());
+    assert_eq!(
+        batch.reason.as_deref(),
+        Some("selector 3 failed: rejected input")
+    );
+    assert_eq!(engine.store().get_value(input), Some(&Value::from("good")));
+    assert_eq!(engine.store().get_value(echo), Some(&Value::from("good!")));
+    assert!(!engine.has_pending_work());
+
+    engine.begin_tick().unwrap();
+    assert!(engine.commit().unwrap().is_empty());
+}
+
+#[test]
+fn engine_quarantines_a_failing_selector_until_its_input_changes() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let other = NodeId::new(2);
+    let checked = NodeId::new(3);
+    engine.register_selector(checked, move |ctx| {
+        let value = ctx.read(input).unwrap_or_default();
+        if value != "ok" {
+            return Err(SelectorError::failed("rejected input"));
+        }
+        Ok(value)
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "bad").unwrap();
+    assert_eq!(engine.commit().unwrap().meta_kind, TickResult::Fallback);
+    assert!(engine.is_quarantined(checked));
+    assert!(!engine.has_pending_work());
+
+    engine.begin_tick().unwrap();
+    engine.set_value(other, "unrelated").unwrap();
+    let batch = engine.commit().unwrap();
+    assert_eq!(batch.meta_kind, TickResult::Commit);
+    assert_eq!(
+        batch.ops,
+        vec![PatchOp::SetText {
+            node: other,
+            text: "unrelated".to_string(),
+        }]
+    );
+    assert_eq!(engine.store().get_value(input), None);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "ok").unwrap();
+    let batch = engine.commit().unwrap();
+    assert_eq!(batch.meta_kind, TickResult::Commit);
+    assert!(batch.ops.contains(&PatchOp::SetText {
+        node: checked,
+        text: "ok".to_string(),
+    }));
+    assert!(!engine.is_quarantined(checked));
+}
+
+#[test]
+fn engine_fallback_restores_the_dependencies_of_selectors_that_ran() {
+    let mut engine = Engine::new();
+    let flag = NodeId::new(1);
+    let x = NodeId::new(2);
+    let y = NodeId::new(3);
+    let pick = NodeId::new(10);
+    let guard = NodeId::new(20);
+    engine.register_selector(pick, move |ctx| {
+        if ctx.read(flag).is_some_and(|value| value == "on") {
+            ctx.read(y).unwrap_or_default()
+        } else {
+            ctx.read(x).unwrap_or_default()
+        }
+    });
+    engine.register_selector(guard, move |ctx| {
+        let picked = ctx.read(pick).unwrap_or_default();
+        if ctx.read(flag).is_some_and(|value| value == "on") {
+            return Err(SelectorError::failed("flag is on"));
+        }
+        Ok(picked)
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(flag, "off").unwrap();
+    engine.set_value(x, "x0").unwrap();
+    engine.set_value(y, "y0").unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(flag, "on").unwrap();
+    assert_eq!(engine.commit().unwrap().meta_kind, TickResult::Fallback);
+    assert_eq!(engine.graph().dependencies_of(pick), vec![flag, x]);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(x, "x1").unwrap();
+    engine.commit().unwrap();
+    assert_eq!(engine.store().get_value(pick), Some(&Value::from("x1")));
+}
+
+#[test]
+fn engine_captures_selector_panics() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    engine.register_selector(NodeId::new(2), move |ctx| {
+        let value = ctx.read(input).unwrap_or_default();
+        if value == "boom" {
+            panic!("boom");
+        }
+        value
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "boom").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(batch.meta_kind, TickResult::Fallback);
+    assert_eq!(batch.reason.as_deref(), Some("selector 2 panicked: boom"));
+    assert_eq!(engine.store().get_value(input), None);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "calm").unwrap();
+    let batch = engine.commit().unwrap();
+    assert_eq!(batch.meta_kind, TickResult::Commit);
+    assert_eq!(batch.len(), 2);
+}
+
+#[test]
+fn engine_resolves_async_selectors_in_a_follow_up_tick() {
+    let mut engine = Engine::new();
+    let query = NodeId::new(1);
+    let results = NodeId::new(2);
+    let heading = NodeId::new(3);
+    engine.register_selector(results, move |ctx| {
+        let query = ctx.read(query).unwrap_or_default();
+        match ctx.resolved() {
+            Some(found) => found,
+            None => ctx.pending(format!("Searching {query}…")),
+        }
+    });
+    engine.register_selector(heading, move |ctx| {
+        format!("Results: {}", ctx.read(results).unwrap_or_default())
+    });
+    assert_eq!(engine.pending_token(results), None);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(query, "rust").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(batch.len(), 3);
+    assert_eq!(
+        engine.store().get_value(heading),
+        Some(&Value::from("Results: Searching rust…"))
+    );
+    assert_eq!(engine.awaiting_selectors(), vec![results]);
+    assert!(!engine.has_pending_work());
+
+    let token = engine.pending_token(results).unwrap();
+    assert_eq!(token.selector(), results);
+    assert!(engine.resolve_selector(token, "3 crates"));
+    assert!(engine.has_pending_work());
+
+    engine.begin_tick().unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!(
+        batch.ops,
+        vec![
+            PatchOp::SetText {
+                node: results,
+                text: "3 crates".to_string(),
+            },
+            PatchOp::SetText {
+                node: heading,
+                text: "Results: 3 crates".to_string(),
+            },
+        ]
+    );
+    assert!(!engine.is_awaiting(results));
+    assert!(!engine.resolve_selector(token, "late"));
+}
+
+#[test]
+fn engine_rejects_resolutions_of_superseded_requests() {
+    let mut engine = Engine::new();
+    let query = NodeId::new(1);
+    let results = NodeId::new(2);
+    engine.register_selector(results, move |ctx| {
+        let query = ctx.read(query).unwrap_or_default();
+        match ctx.resolved() {
+            Some(found) => found,
+            None => ctx.pending(format!("loading {query}")),
+        }
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(query, "a").unwrap();
+    engine.commit().unwrap();
+    let stale = engine.pending_token(results).unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(query, "ab").unwrap();
+    engine.commit().unwrap();
+    let current = engine.pending_token(results).unwrap();
+
+    assert_ne!(stale, current);
+    assert!(!engine.resolve_selector(stale, "results for a"));
+    assert!(!engine.has_pending_work());
+    assert_eq!(
+        engine.store().get_value(results),
+        Some(&Value::from("loading ab"))
+    );
+
+    assert!(engine.resolve_selector(current, "results for ab"));
+    engine.begin_tick().unwrap();
+    engine.commit().unwrap();
+    assert_eq!(
+        engine.store().get_value(results),
+        Some(&Value::from("results for ab"))
+    );
+}
+
+#[test]
+fn engine_updates_only_rerun_selectors_reading_changed_paths() {
+    let mut engine = Engine::new();
+    let state = NodeId::new(1);
+    let count = NodeId::new(10);
+    let filter = NodeId::new(20);
+    let count_runs = Rc::new(Cell::new(0));
+    let filter_runs = Rc::new(Cell::new(0));
+
+    let counter = count_runs.clone();
+    engine.register_selector(count, move |ctx| {
+        counter.set(counter.get() + 1);
+        let items = ctx.read_path(state, "items").unwrap_or_default();
+        items.as_list().map_or(0, <[Value]>::len) as i64
+    });
+    let counter = filter_runs.clone();
+    engine.register_selector(filter, move |ctx| {
+        counter.set(counter.get() + 1);
+        ctx.read_path(state, "filter").unwrap_or_default()
+    });
+
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| {
+            tx.set_field(state, "filter", "open")?;
+            tx.set_field(state, "items", vec![1, 2])
+        })
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+    assert_eq!((count_runs.get(), filter_runs.get()), (1, 1));
+
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| tx.push(state, "items", 3))
+        .unwrap()
+        .unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert_eq!((count_runs.get(), filter_runs.get()), (2, 1));
+    assert!(batch.ops.contains(&PatchOp::SetText {
+        node: count,
+        text: "3".to_string(),
+    }));
+    assert_eq!(
+        engine
+            .store()
+            .get_value(state)
+            .and_then(|v| v.get_path(&"items".into())),
+        Some(&Value::from(vec![1, 2, 3]))
+    );
+
+    engine.begin_tick().unwrap();
+    let removed = engine
+        .update(|tx| tx.splice(state, "items", 0, 2, Vec::new()))
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+
+    assert_eq!(removed, vec![Value::from(1), Value::from(2)]);
+    assert_eq!(engine.store().get_value(count), Some(&Value::from(1)));
+    assert_eq!((count_runs.get(), filter_runs.get()), (3, 1));
+
+    // Replacing the whole value re-runs every reader.
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| {
+            tx.set(state, Value::Null);
+            Ok::<_, PathError>(())
+        })
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+    assert_eq!((count_runs.get(), filter_runs.get()), (4, 2));
+}
+
+#[test]
+fn engine_reruns_selectors_reading_numeric_map_keys() {
+    let mut engine = Engine::new();
+    let state = NodeId::new(1);
+    let third = NodeId::new(10);
+    engine.register_selector(third, move |ctx| {
+        ctx.read_path(state, Path::root().key("3"))
+            .unwrap_or_default()
+    });
+
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| tx.set_field(state, "3", "a"))
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| tx.set_field(state, "3", "b"))
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+
+    assert_eq!(engine.store().get_value(third), Some(&Value::from("b")));
+}
+
+#[test]
+fn engine_rollback_discards_path_updates() {
+    let mut engine = Engine::new();
+    let state = NodeId::new(1);
+    let count = NodeId::new(10);
+    engine.register_selector(count, move |ctx| {
+        let items = ctx.read_path(state, "items").unwrap_or_default();
+        items.as_list().map_or(0, <[Value]>::len) as i64
+    });
+
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| tx.set_field(state, "items", vec![1]))
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    let result = engine
+        .update(|tx| {
+            tx.push(state, "items", 2)?;
+            tx.push(state, "missing", 3)
+        })
+        .unwrap();
+    assert!(result.is_err());
+    engine.rollback("discard").unwrap();
+
+    assert!(!engine.has_pending_work());
+    assert_eq!(engine.store().get_value(count), Some(&Value::from(1)));
+    assert_eq!(
+        engine
+            .store()
+            .get_value(state)
+            .and_then(|v| v.get_path(&"items".into())),
+        Some(&Value::from(vec![1]))
+    );
+    assert_eq!(
+        engine.update(|_| Ok::<_, PathError>(())),
+        Err(SchedulerError::TickNotStarted)
+    );
+}
+
+#[test]
+fn engine_failed_path_edit_stages_nothing() {
+    let mut engine = Engine::new();
+    let missing = NodeId::new(9);
+
+    engine.begin_tick().unwrap();
+    let result = engine
+        .update(|tx| {
+            let pushed = tx.push(missing, "items", 1);
+            assert_eq!(tx.get(missing), None);
+            pushed
+        })
+        .unwrap();
+    assert!(result.is_err());
+    let batch = engine.commit().unwrap();
+
+    assert!(batch.is_empty());
+    assert_eq!(engine.store().get_value(missing), None);
+}
+
+#[test]
+fn engine_update_drops_staged_writes_when_it_fails() {
+    let mut engine = Engine::new();
+    let state = NodeId::new(1);
+    let count = NodeId::new(10);
+    engine.register_selector(count, move |ctx| {
+        let items = ctx.read_path(state, "items").unwrap_or_default();
+        items.as_list().map_or(0, <[Value]>::len) as i64
+    });
+
+    engine.begin_tick().unwrap();
+    engine
+        .update(|tx| tx.set_field(state, "items", vec![1]))
+        .unwrap()
+        .unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    let result = engine
+        .update(|tx| {
+            tx.push(state, "items", 2)?;
+            tx.set(NodeId::new(2), "extra");
+            tx.push(state, "missing", 3)
+        })
+        .unwrap();
+    assert!(result.is_err());
+    let batch = engine.commit().unwrap();
+
+    assert!(batch.is_empty());
+    assert_eq!(engine.store().get_value(count), Some(&Value::from(1)));
+    assert_eq!(engine.store().get_value(NodeId::new(2)), None);
+}
+
+#[test]
+fn engine_fallback_restores_the_tick_snapshot() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    let echo = NodeId::new(10);
+    let guard = NodeId::new(20);
+    engine.register_selector(echo, move |ctx| ctx.read(input).unwrap_or_default());
+    engine.register_selector(guard, move |ctx| {
+        let echoed = ctx.read(echo).unwrap_or_default();
+        if echoed == "bad" {
+            Err(SelectorError::failed("rejected"))
+        } else {
+            Ok(echoed)
+        }
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "good").unwrap();
+    engine.commit().unwrap();
+    let before = engine.store().snapshot();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "bad").unwrap();
+    assert!(!engine.commit().unwrap().is_commit());
+    assert_eq!(engine.store().get_value(echo), Some(&Value::from("good")));
+    assert_eq!(engine.store().version(echo), before.version(echo));
+
+    // The output cached during the abandoned tick must not be reused.
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "fine").unwrap();
+    engine.commit().unwrap();
+    assert_eq!(engine.store().get_value(guard), Some(&Value::from("fine")));
+    assert_eq!(before.get_value(guard), Some(&Value::from("good")));
+}
@@ -1,4 +1,6 @@
-use crust_core::{DependencyGraph, NodeId};
+use std::collections::BTreeSet;
+
+use crust_core::{DependencyGraph, GraphError, NodeId};
 
 #[test]
 fn dependents_are_deterministic() {
@@ -16,3 +18,125 @@ fn dependents_are_deterministic() {
     assert_eq!(graph_a.dependents_of(NodeId::new(1)), expected);
     assert_eq!(graph_b.dependents_of(NodeId::new(1)), expected);
 }
+
+#[test]
+fn remove_edge_only_drops_that_edge() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(1), NodeId::new(2));
+    graph.add_edge(NodeId::new(1), NodeId::new(3));
+
+    assert!(graph.remove_edge(NodeId::new(1), NodeId::new(2)));
+    assert!(!graph.remove_edge(NodeId::new(1), NodeId::new(2)));
+
+    assert_eq!(graph.dependents_of(NodeId::new(1)), vec![NodeId::new(3)]);
+    assert!(graph.contains_node(NodeId::new(2)));
+}
+
+#[test]
+fn remove_node_clears_incoming_and_outgoing_edges() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(1), NodeId::new(2));
+    graph.add_edge(NodeId::new(2), NodeId::new(3));
+
+    graph.remove_node(NodeId::new(2));
+
+    assert!(!graph.contains_node(NodeId::new(2)));
+    assert!(graph.dependents_of(NodeId::new(1)).is_empty());
+    assert!(graph.dependents_of(NodeId::new(2)).is_empty());
+}
+
+#[test]
+fn transitive_dependents_follow_every_level() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(1), NodeId::new(2));
+    graph.add_edge(NodeId::new(2), NodeId::new(3));
+    graph.add_edge(NodeId::new(2), NodeId::new(4));
+    graph.add_edge(NodeId::new(5), NodeId::new(4));
+
+    let reached: Vec<NodeId> = graph
+        .transitive_dependents(NodeId::new(1))
+        .into_iter()
+        .collect();
+
+    assert_eq!(
+        reached,
+        vec![NodeId::new(2), NodeId::new(3), NodeId::new(4)]
+    );
+}
+
+#[test]
+fn topological_order_puts_sources_first_and_breaks_ties_by_id() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(9), NodeId::new(2));
+    graph.add_edge(NodeId::new(2), NodeId::new(1));
+    graph.add_edge(NodeId::new(7), NodeId::new(1));
+    let nodes: BTreeSet<NodeId> = [1, 2, 7, 9].into_iter().map(NodeId::new).collect();
+
+    let order = graph.topological_order(&nodes).unwrap();
+
+    assert_eq!(
+        order,
+        vec![
+            NodeId::new(7),
+            NodeId::new(9),
+            NodeId::new(2),
+            NodeId::new(1)
+        ]
+    );
+    assert_eq!(graph.ensure_acyclic(), Ok(()));
+}
+
+#[test]
+fn cycles_are_reported_with_their_path() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(1), NodeId::new(2));
+    graph.add_edge(NodeId::new(2), NodeId::new(3));
+    graph.add_edge(NodeId::new(3), NodeId::new(4));
+    graph.add_edge(NodeId::new(4), NodeId::new(2));
+
+    let cycle = GraphError::Cycle(vec![
+        NodeId::new(2),
+        NodeId::new(3),
+        NodeId::new(4),
+        NodeId::new(2),
+    ]);
+    assert_eq!(graph.ensure_acyclic(), Err(cycle));
+    let outside: BTreeSet<NodeId> = [1, 2, 3].into_iter().map(NodeId::new).collect();
+    assert!(graph.topological_order(&outside).is_ok());
+}
+
+#[test]
+fn dependencies_and_counts_reflect_both_directions() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(1), NodeId::new(3));
+    graph.add_edge(NodeId::new(2), NodeId::new(3));
+    graph.add_edge(NodeId::new(3), NodeId::new(4));
+
+    assert_eq!(
+        graph.dependencies_of(NodeId::new(3)),
+        vec![NodeId::new(1), NodeId::new(2)]
+    );
+    assert_eq!(graph.node_count(), 4);
+    assert_eq!(graph.edge_count(), 3);
+
+    graph.remove_node(NodeId::new(1));
+    assert_eq!(graph.dependencies_of(NodeId::new(3)), vec![NodeId::new(2)]);
+    assert_eq!(graph.node_count(), 3);
+    assert_eq!(graph.edge_count(), 2);
+}
+
+#[test]
+fn graph_exports_to_dot_and_json() {
+    let mut graph = DependencyGraph::new();
+    graph.add_edge(NodeId::new(2), NodeId::new(5));
+    graph.add_edge(NodeId::new(1), NodeId::new(5));
+
+    assert_eq!(
+        graph.to_dot(),
+        "digraph dependencies {\n  1;\n  2;\n  5;\n  1 -> 5;\n  2 -> 5;\n}\n"
+    );
+    assert_eq!(
+        graph.to_json(),
+        r#"{"nodes":[1,2,5],"edges":[{"source":1,"dependent":5},{"source":2,"dependent":5}]}"#
+    );
+}
@@ -0,0 +1,93 @@
+use crust_core::{NodeId, NodeIdAllocator, PatchOp};
+
+#[test]
+fn keys_keep_their_id_across_ticks() {
+    let mut ids = NodeIdAllocator::new();
+
+    let list = ids.id_for_key("root:list");
+    let row = ids.id_for_key("root:list:row-1");
+
+    assert_eq!(list, NodeId::new(1));
+    assert_eq!(row, NodeId::new(2));
+    assert_eq!(ids.id_for_key("root:list"), list);
+    assert_eq!(ids.key_of(row), Some("root:list:row-1"));
+    assert_eq!(ids.allocate(), NodeId::new(3));
+}
+
+#[test]
+fn removed_ids_are_recycled_only_after_reclaim() {
+    let mut ids = NodeIdAllocator::starting_at(10);
+    let a = ids.id_for_key("a");
+    let b = ids.id_for_key("b");
+    ids.id_for_key("c");
+
+    ids.release_removed(&[PatchOp::Remove { node: b }, PatchOp::Remove { node: a }]);
+    assert_eq!(ids.get("a"), None);
+    assert_eq!(ids.allocate(), NodeId::new(13));
+
+    ids.reclaim();
+    assert_eq!(ids.id_for_key("d"), a);
+    assert_eq!(ids.id_for_key("a"), b);
+    assert_eq!(ids.allocate(), NodeId::new(14));
+    assert!(!ids.release(NodeId::new(99)));
+    assert!(!ids.release(NodeId::new(3)));
+    ids.reclaim();
+    assert_eq!(ids.allocate(), NodeId::new(15));
+}
+
+#[test]
+fn allocation_is_deterministic_for_replay() {
+    let run = || {
+        let mut ids = NodeIdAllocator::new();
+        let mut seen = Vec::new();
+        for tick in 0..4 {
+            for row in 0..3 {
+                seen.push(ids.id_for_key(&format!("row-{}", tick + row)));
+            }
+            ids.release(ids.get(&format!("row-{tick}")).unwrap());
+            ids.reclaim();
+        }
+        seen
+    };
+
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn removing_a_node_releases_its_descendants() {
+    let mut ids = NodeIdAllocator::new();
+    let list = ids.id_for_key("list");
+    let row = ids.id_for_key("list:row");
+    let label = ids.id_for_key("list:row:label");
+    let other = ids.id_for_key("other");
+
+    ids.release_removed(&[
+        PatchOp::Insert {
+            parent: list,
+            child: row,
+        },
+        PatchOp::Insert {
+            parent: row,
+            child: label,
+        },
+        PatchOp::Insert {
+            parent: list,
+            child: other,
+        },
+    ]);
+    // Moving `other` out of the list keeps it alive when the list goes.
+    ids.release_removed(&[
+        PatchOp::InsertBefore {
+            parent: NodeId::new(0),
+            child: other,
+            before: list,
+        },
+        PatchOp::Remove { node: list },
+    ]);
+
+    assert_eq!(ids.get("list"), None);
+    assert_eq!(ids.get("list:row"), None);
+    assert_eq!(ids.get("list:row:label"), None);
+    assert_eq!(ids.get("other"), Some(other));
+    assert_eq!(ids.len(), 1);
+}
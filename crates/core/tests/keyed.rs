@@ -0,0 +1,123 @@
+use crust_core::{diff_keyed_children, KeyedChild, NodeId, PatchOp};
+
+fn parent() -> NodeId {
+    NodeId::new(0)
+}
+
+fn children(keys: &str) -> Vec<KeyedChild<char>> {
+    keys.chars()
+        .map(|key| KeyedChild::new(key, NodeId::new(key as u64)))
+        .collect()
+}
+
+/// Applies structural ops to a flat child list the way the JS host does.
+fn apply(mut list: Vec<NodeId>, ops: &[PatchOp]) -> Vec<NodeId> {
+    for op in ops {
+        match op {
+            PatchOp::Remove { node } => list.retain(|existing| existing != node),
+            PatchOp::Insert { child, .. } => {
+                list.retain(|existing| existing != child);
+                list.push(*child);
+            }
+            PatchOp::InsertBefore { child, before, .. } => {
+                list.retain(|existing| existing != child);
+                let index = list.iter().position(|existing| existing == before).unwrap();
+                list.insert(index, *child);
+            }
+            other => panic!("unexpected op {other:?}"),
+        }
+    }
+    list
+}
+
+fn check(old: &str, new: &str) -> Vec<PatchOp> {
+    let old = children(old);
+    let new = children(new);
+    let ops = diff_keyed_children(parent(), &old, &new);
+    let start = old.iter().map(|child| child.node).collect();
+    let expected: Vec<NodeId> = new.iter().map(|child| child.node).collect();
+    assert_eq!(apply(start, &ops), expected);
+    ops
+}
+
+#[test]
+fn unchanged_list_emits_nothing() {
+    assert!(check("ABCD", "ABCD").is_empty());
+}
+
+#[test]
+fn rotation_moves_a_single_child() {
+    let ops = check("ABC", "CAB");
+
+    assert_eq!(
+        ops,
+        vec![PatchOp::InsertBefore {
+            parent: parent(),
+            child: NodeId::new('C' as u64),
+            before: NodeId::new('A' as u64),
+        }]
+    );
+}
+
+#[test]
+fn removals_and_inserts_touch_only_changed_keys() {
+    let ops = check("ABCD", "ABXD");
+
+    assert_eq!(
+        ops,
+        vec![
+            PatchOp::Remove {
+                node: NodeId::new('C' as u64),
+            },
+            PatchOp::InsertBefore {
+                parent: parent(),
+                child: NodeId::new('X' as u64),
+                before: NodeId::new('D' as u64),
+            },
+        ]
+    );
+}
+
+#[test]
+fn append_uses_insert() {
+    let ops = check("AB", "ABC");
+
+    assert_eq!(
+        ops,
+        vec![PatchOp::Insert {
+            parent: parent(),
+            child: NodeId::new('C' as u64),
+        }]
+    );
+}
+
+#[test]
+fn moves_are_minimal_for_reversal_and_shuffles() {
+    assert_eq!(check("ABCDE", "EDCBA").len(), 4);
+    assert_eq!(check("ABCDEFG", "BCDEFGA").len(), 1);
+    assert_eq!(check("ABCDEFGH", "AHCDEFGB").len(), 2);
+    check("ABCDEFGHIJ", "JXBDCYFEAZ");
+    check("", "ABC");
+    check("ABC", "");
+}
+
+#[test]
+fn key_mapped_to_new_node_is_replaced() {
+    let old = vec![KeyedChild::new("row", NodeId::new(1))];
+    let new = vec![KeyedChild::new("row", NodeId::new(2))];
+
+    let ops = diff_keyed_children(parent(), &old, &new);
+
+    assert_eq!(
+        ops,
+        vec![
+            PatchOp::Remove {
+                node: NodeId::new(1),
+            },
+            PatchOp::Insert {
+                parent: parent(),
+                child: NodeId::new(2),
+            },
+        ]
+    );
+}
@@ -1,4 +1,6 @@
-use crust_core::{EffectQueue, NodeId, PatchOp};
+use std::collections::BTreeMap;
+
+use crust_core::{EffectQueue, NodeId, PatchBatch, PatchOp};
 
 #[test]
 fn effect_queue_preserves_order_and_clears() {
@@ -19,3 +21,526 @@ fn effect_queue_preserves_order_and_clears() {
     assert_eq!(batch, vec![first, second]);
     assert!(queue.pending().is_empty());
 }
+
+#[test]
+fn effect_queue_keeps_last_write_per_target() {
+    let mut queue = EffectQueue::new();
+    let node = NodeId::new(1);
+    for text in ["a", "b", "c"] {
+        queue.push(PatchOp::SetText {
+            node,
+            text: text.to_string(),
+        });
+    }
+    queue.push(PatchOp::SetAttr {
+        node,
+        name: "class".to_string(),
+        value: "old".to_string(),
+    });
+    queue.push(PatchOp::SetAttr {
+        node,
+        name: "id".to_string(),
+        value: "main".to_string(),
+    });
+    queue.push(PatchOp::SetAttr {
+        node,
+        name: "class".to_string(),
+        value: "new".to_string(),
+    });
+
+    let batch = queue.commit();
+
+    assert_eq!(
+        batch,
+        vec![
+            PatchOp::SetText {
+                node,
+                text: "c".to_string(),
+            },
+            PatchOp::SetAttr {
+                node,
+                name: "id".to_string(),
+                value: "main".to_string(),
+            },
+            PatchOp::SetAttr {
+                node,
+                name: "class".to_string(),
+                value: "new".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn effect_queue_drops_ops_on_removed_nodes() {
+    let mut queue = EffectQueue::new();
+    let parent = NodeId::new(1);
+    let doomed = NodeId::new(2);
+    // Attached before this batch, so moving it and then removing it must
+    // still remove it.
+    let fresh = NodeId::new(3);
+
+    queue.push(PatchOp::SetAttr {
+        node: doomed,
+        name: "class".to_string(),
+        value: "x".to_string(),
+    });
+    queue.push(PatchOp::Insert {
+        parent,
+        child: fresh,
+    });
+    queue.push(PatchOp::SetText {
+        node: parent,
+        text: "kept".to_string(),
+    });
+    queue.push(PatchOp::Remove { node: doomed });
+    queue.push(PatchOp::Remove { node: fresh });
+
+    let batch = queue.commit();
+
+    assert_eq!(
+        batch,
+        vec![
+            PatchOp::Insert {
+                parent,
+                child: fresh,
+            },
+            PatchOp::SetText {
+                node: parent,
+                text: "kept".to_string(),
+            },
+            PatchOp::Remove { node: doomed },
+            PatchOp::Remove { node: fresh },
+        ]
+    );
+}
+
+#[test]
+fn effect_queue_keeps_removes_of_nodes_ensured_in_the_batch() {
+    let mut queue = EffectQueue::new();
+    let parent = NodeId::new(1);
+    let temp = NodeId::new(2);
+
+    queue.push(PatchOp::EnsureNode {
+        node: temp,
+        tag: "span".to_string(),
+    });
+    queue.push(PatchOp::Insert {
+        parent,
+        child: temp,
+    });
+    queue.push(PatchOp::SetText {
+        node: temp,
+        text: "gone".to_string(),
+    });
+    queue.push(PatchOp::Remove { node: temp });
+
+    // `temp` may have existed before the batch, so it must still be removed.
+    assert_eq!(queue.commit(), vec![PatchOp::Remove { node: temp }]);
+}
+
+#[test]
+fn effect_queue_keeps_writes_after_remove() {
+    let mut queue = EffectQueue::new();
+    let node = NodeId::new(1);
+
+    queue.push(PatchOp::Remove { node });
+    queue.push(PatchOp::Insert {
+        parent: NodeId::new(9),
+        child: node,
+    });
+    queue.push(PatchOp::SetText {
+        node,
+        text: "back".to_string(),
+    });
+
+    let batch = queue.commit();
+
+    assert_eq!(batch.len(), 3);
+}
+
+#[test]
+fn patch_ops_report_host_kinds() {
+    let node = NodeId::new(1);
+    let ops = [
+        PatchOp::EnsureNode {
+            node,
+            tag: "div".to_string(),
+        },
+        PatchOp::RemoveAttr {
+            node,
+            name: "hidden".to_string(),
+        },
+        PatchOp::Insert {
+            parent: node,
+            child: NodeId::new(2),
+        },
+        PatchOp::InsertBefore {
+            parent: node,
+            child: NodeId::new(3),
+            before: NodeId::new(2),
+        },
+    ];
+
+    let kinds: Vec<_> = ops.iter().map(PatchOp::kind).collect();
+    assert_eq!(
+        kinds,
+        vec!["EnsureNode", "RemoveAttr", "AppendChild", "InsertBefore"]
+    );
+}
+
+#[test]
+fn patch_batch_fingerprints_are_pinned() {
+    let node = NodeId::new(1);
+    let batch = PatchBatch::commit(vec![
+        PatchOp::EnsureNode {
+            node,
+            tag: "div".to_string(),
+        },
+        PatchOp::SetText {
+            node,
+            text: "hi".to_string(),
+        },
+        PatchOp::Insert {
+            parent: NodeId::new(0),
+            child: node,
+        },
+    ]);
+
+    // FNV-1a 64 over the wire encoding of the ops.
+    assert_eq!(
+        batch.with_fingerprint().fingerprint,
+        Some(0x366a_284f_3440_eeff)
+    );
+    assert_eq!(
+        PatchBatch::commit(Vec::new())
+            .with_fingerprint()
+            .fingerprint,
+        Some(0x0832_8807_b4eb_6fed)
+    );
+}
+
+#[test]
+fn effect_queue_remove_attr_overrides_set_attr() {
+    let mut queue = EffectQueue::new();
+    let node = NodeId::new(1);
+
+    queue.push(PatchOp::EnsureNode {
+        node,
+        tag: "div".to_string(),
+    });
+    queue.push(PatchOp::SetAttr {
+        node,
+        name: "hidden".to_string(),
+        value: "true".to_string(),
+    });
+    queue.push(PatchOp::RemoveAttr {
+        node,
+        name: "hidden".to_string(),
+    });
+
+    let batch = queue.commit();
+
+    assert_eq!(
+        batch,
+        vec![
+            PatchOp::EnsureNode {
+                node,
+                tag: "div".to_string(),
+            },
+            PatchOp::RemoveAttr {
+                node,
+                name: "hidden".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn effect_queue_drops_writes_beneath_removed_nodes() {
+    let mut queue = EffectQueue::new();
+    let page = NodeId::new(1);
+    let panel = NodeId::new(2);
+    let label = NodeId::new(3);
+    let existing = NodeId::new(4);
+
+    queue.push(PatchOp::EnsureNode {
+        node: panel,
+        tag: "div".to_string(),
+    });
+    queue.push(PatchOp::EnsureNode {
+        node: label,
+        tag: "span".to_string(),
+    });
+    queue.push(PatchOp::Insert {
+        parent: panel,
+        child: label,
+    });
+    queue.push(PatchOp::Insert {
+        parent: page,
+        child: panel,
+    });
+    queue.push(PatchOp::Remove { node: panel });
+
+    assert_eq!(
+        queue.commit(),
+        vec![
+            PatchOp::EnsureNode {
+                node: panel,
+                tag: "div".to_string(),
+            },
+            PatchOp::EnsureNode {
+                node: label,
+                tag: "span".to_string(),
+            },
+            PatchOp::Insert {
+                parent: panel,
+                child: label,
+            },
+            PatchOp::Remove { node: panel },
+        ]
+    );
+
+    queue.push(PatchOp::EnsureNode {
+        node: panel,
+        tag: "div".to_string(),
+    });
+    queue.push(PatchOp::Insert {
+        parent: page,
+        child: panel,
+    });
+    queue.push(PatchOp::InsertBefore {
+        parent: panel,
+        child: existing,
+        before: label,
+    });
+    queue.push(PatchOp::SetText {
+        node: existing,
+        text: "moved".to_string(),
+    });
+    queue.push(PatchOp::Remove { node: panel });
+
+    assert_eq!(
+        queue.commit(),
+        vec![
+            PatchOp::EnsureNode {
+                node: panel,
+                tag: "div".to_string(),
+            },
+            PatchOp::Insert {
+                parent: page,
+                child: panel,
+            },
+            PatchOp::InsertBefore {
+                parent: panel,
+                child: existing,
+                before: label,
+            },
+            PatchOp::Remove { node: panel },
+        ]
+    );
+}
+
+#[test]
+fn effect_queue_commit_matches_applying_every_op() {
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..20_000 {
+        let before = Dom::random(&mut rng);
+        let mut expected = before.clone();
+        let mut queue = EffectQueue::new();
+        for _ in 0..rng.below(12) + 1 {
+            let op = expected.random_op(&mut rng);
+            expected.apply(&op).expect("generated ops are valid");
+            queue.push(op);
+        }
+        let pushed = queue.pending().to_vec();
+
+        let mut actual = before.clone();
+        for op in queue.commit() {
+            if let Err(err) = actual.apply(&op) {
+                panic!("{err} applying the coalesced {pushed:?} to {before:?}");
+            }
+        }
+        assert_eq!(actual, expected, "coalescing {pushed:?} on {before:?}");
+    }
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+
+    fn node(&mut self) -> NodeId {
+        NodeId::new(self.below(8) + 1)
+    }
+}
+
+/// The host tree as `packages/js-host` models it.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Dom {
+    nodes: BTreeMap<NodeId, DomNode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DomNode {
+    tag: String,
+    text: String,
+    attrs: BTreeMap<String, String>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Dom {
+    fn random(rng: &mut XorShift) -> Self {
+        let mut dom = Dom::default();
+        for raw in 1..=8 {
+            if rng.below(3) == 0 {
+                continue;
+            }
+            let node = NodeId::new(raw);
+            dom.apply(&PatchOp::EnsureNode {
+                node,
+                tag: "div".to_string(),
+            })
+            .unwrap();
+            let parent = rng.node();
+            if parent < node && dom.nodes.contains_key(&parent) {
+                dom.apply(&PatchOp::Insert {
+                    parent,
+                    child: node,
+                })
+                .unwrap();
+            }
+        }
+        dom
+    }
+
+    /// An op that is valid on this tree.
+    fn random_op(&self, rng: &mut XorShift) -> PatchOp {
+        let node = rng.node();
+        let other = rng.node();
+        let text = ["a", "b"][rng.below(2) as usize].to_string();
+        let op = match rng.below(8) {
+            0 => PatchOp::Remove { node },
+            1 => PatchOp::SetText { node, text },
+            2 => PatchOp::SetAttr {
+                node,
+                name: "class".to_string(),
+                value: text,
+            },
+            3 => PatchOp::RemoveAttr {
+                node,
+                name: "class".to_string(),
+            },
+            4 => PatchOp::Insert {
+                parent: other,
+                child: node,
+            },
+            5 => PatchOp::InsertBefore {
+                parent: other,
+                child: node,
+                before: rng.node(),
+            },
+            _ => PatchOp::EnsureNode {
+                node,
+                tag: ["div", "span"][rng.below(2) as usize].to_string(),
+            },
+        };
+        if self.clone().apply(&op).is_ok() {
+            op
+        } else {
+            PatchOp::EnsureNode {
+                node,
+                tag: "li".to_string(),
+            }
+        }
+    }
+
+    fn apply(&mut self, op: &PatchOp) -> Result<(), String> {
+        match op {
+            PatchOp::EnsureNode { node, tag } => {
+                self.nodes.entry(*node).or_insert_with(|| DomNode {
+                    tag: tag.clone(),
+                    text: String::new(),
+                    attrs: BTreeMap::new(),
+                    parent: None,
+                    children: Vec::new(),
+                });
+            }
+            PatchOp::SetText { node, text } => self.get(*node)?.text = text.clone(),
+            PatchOp::SetAttr { node, name, value } => {
+                self.get(*node)?.attrs.insert(name.clone(), value.clone());
+            }
+            PatchOp::RemoveAttr { node, name } => {
+                self.get(*node)?.attrs.remove(name);
+            }
+            PatchOp::Insert { parent, child } => self.insert(*parent, *child, None)?,
+            PatchOp::InsertBefore {
+                parent,
+                child,
+                before,
+            } => self.insert(*parent, *child, Some(*before))?,
+            PatchOp::Remove { node } => {
+                if self.nodes.contains_key(node) {
+                    self.detach(*node);
+                    self.destroy(*node);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, node: NodeId) -> Result<&mut DomNode, String> {
+        self.nodes
+            .get_mut(&node)
+            .ok_or_else(|| format!("unknown node {}", node.raw()))
+    }
+
+    fn insert(
+        &mut self,
+        parent: NodeId,
+        child: NodeId,
+        before: Option<NodeId>,
+    ) -> Result<(), String> {
+        self.get(child)?;
+        let mut ancestor = Some(parent);
+        while let Some(node) = ancestor {
+            if node == child {
+                return Err(format!(
+                    "cycle inserting {} into {}",
+                    child.raw(),
+                    parent.raw()
+                ));
+            }
+            ancestor = self.get(node)?.parent;
+        }
+        self.detach(child);
+        self.get(child)?.parent = Some(parent);
+        let siblings = &mut self.get(parent)?.children;
+        match before.and_then(|before| siblings.iter().position(|&node| node == before)) {
+            Some(index) => siblings.insert(index, child),
+            None => siblings.push(child),
+        }
+        Ok(())
+    }
+
+    fn detach(&mut self, node: NodeId) {
+        if let Some(parent) = self.nodes[&node].parent {
+            let siblings = &mut self.nodes.get_mut(&parent).unwrap().children;
+            siblings.retain(|&sibling| sibling != node);
+            self.nodes.get_mut(&node).unwrap().parent = None;
+        }
+    }
+
+    fn destroy(&mut self, node: NodeId) {
+        let removed = self.nodes.remove(&node).unwrap();
+        for child in removed.children {
+            self.destroy(child);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crust_core::{Path, PathError, PathSegment, Value};
+
+#[test]
+fn paths_parse_keys_and_indices() {
+    let path = Path::from("items.3.title");
+
+    assert_eq!(
+        path.segments(),
+        &[
+            PathSegment::Key("items".to_string()),
+            PathSegment::Index(3),
+            PathSegment::Key("title".to_string()),
+        ]
+    );
+    assert_eq!(path, Path::root().key("items").index(3).key("title"));
+    assert!(Path::from("").is_root());
+    assert!(path.overlaps(&Path::from("items")));
+    assert!(Path::from("items").overlaps(&path));
+    assert!(!path.overlaps(&Path::from("items.4")));
+    assert!(!path.overlaps(&Path::from("filter")));
+}
+
+#[test]
+fn numeric_keys_overlap_the_matching_index() {
+    let keyed = Path::root().key("rows").key("3");
+
+    assert!(keyed.overlaps(&Path::from("rows.3")));
+    assert!(Path::from("rows.3.title").overlaps(&keyed));
+    assert!(!keyed.overlaps(&Path::from("rows.4")));
+    assert!(!Path::root().key("03").overlaps(&Path::from("3")));
+}
+
+#[test]
+fn values_update_in_place_along_paths() {
+    let mut value = Value::Null;
+
+    value
+        .set_path(&Path::from("filter"), Value::from("open"))
+        .unwrap();
+    assert_eq!(
+        value.push_path(&Path::from("items"), Value::from(1)),
+        Err(PathError::Missing(Path::from("items")))
+    );
+    value
+        .set_path(&Path::from("items"), Value::from(vec![1, 2]))
+        .unwrap();
+    assert_eq!(value.push_path(&Path::from("items"), Value::from(3)), Ok(2));
+    value
+        .set_path(&Path::from("items.0"), Value::from(10))
+        .unwrap();
+
+    let removed = value
+        .splice_path(
+            &Path::from("items"),
+            1,
+            1,
+            vec![Value::from(20), Value::from(21)],
+        )
+        .unwrap();
+
+    assert_eq!(removed, vec![Value::from(2)]);
+    assert_eq!(
+        value.get_path(&Path::from("items")),
+        Some(&Value::from(vec![10, 20, 21, 3]))
+    );
+    assert_eq!(
+        value.get_path(&Path::from("filter")),
+        Some(&Value::from("open"))
+    );
+    assert_eq!(value.get_path(&Path::from("items.9")), None);
+}
+
+#[test]
+fn path_updates_report_bad_targets() {
+    let mut value = Value::from(vec![1, 2]);
+
+    assert_eq!(
+        value.set_path(&Path::from("5"), Value::Null),
+        Err(PathError::OutOfRange(Path::from("5")))
+    );
+    assert_eq!(
+        value.set_path(&Path::from("name"), Value::Null),
+        Err(PathError::TypeMismatch(Path::root()))
+    );
+    assert_eq!(
+        value.splice_path(&Path::root(), 1, 4, Vec::new()),
+        Err(PathError::OutOfRange(Path::root()))
+    );
+    assert_eq!(
+        value.push_path(&Path::from("0"), Value::Null),
+        Err(PathError::TypeMismatch(Path::from("0")))
+    );
+    assert_eq!(value, Value::from(vec![1, 2]));
+}
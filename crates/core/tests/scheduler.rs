@@ -1,4 +1,4 @@
-use crust_core::{NodeId, PatchOp, Scheduler, SchedulerError};
+use crust_core::{Lane, NodeId, PatchOp, Scheduler, SchedulerError};
 
 #[test]
 fn scheduler_coalesces_ops_into_single_batch() {
@@ -53,3 +53,201 @@ fn scheduler_resets_for_next_tick() {
     let batch = scheduler.commit_tick().unwrap();
     assert_eq!(batch.len(), 1);
 }
+
+#[test]
+fn scheduler_keeps_enqueue_order_across_lanes() {
+    let mut scheduler = Scheduler::new();
+    scheduler.begin_tick().unwrap();
+    scheduler
+        .enqueue_op_in(
+            Lane::Background,
+            PatchOp::SetText {
+                node: NodeId::new(1),
+                text: "feed".to_string(),
+            },
+        )
+        .unwrap();
+    scheduler
+        .enqueue_op(PatchOp::SetText {
+            node: NodeId::new(2),
+            text: "typed".to_string(),
+        })
+        .unwrap();
+
+    let batch = scheduler.commit_tick().unwrap();
+
+    assert_eq!(
+        batch,
+        vec![
+            PatchOp::SetText {
+                node: NodeId::new(1),
+                text: "feed".to_string(),
+            },
+            PatchOp::SetText {
+                node: NodeId::new(2),
+                text: "typed".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn scheduler_carries_lanes_over_budget_to_next_tick() {
+    let mut scheduler = Scheduler::new();
+    scheduler.begin_tick().unwrap();
+    scheduler
+        .enqueue_op(PatchOp::Remove {
+            node: NodeId::new(1),
+        })
+        .unwrap();
+    for raw in 10..13 {
+        scheduler
+            .enqueue_op_in(
+                Lane::Background,
+                PatchOp::Remove {
+                    node: NodeId::new(raw),
+                },
+            )
+            .unwrap();
+    }
+
+    let urgent = scheduler.commit_tick_with_budget(2).unwrap();
+    assert_eq!(
+        urgent,
+        vec![
+            PatchOp::Remove {
+                node: NodeId::new(1),
+            },
+            PatchOp::Remove {
+                node: NodeId::new(10),
+            },
+        ]
+    );
+    assert_eq!(scheduler.pending_in(Lane::Background).len(), 2);
+
+    scheduler.begin_tick().unwrap();
+    let carried = scheduler.commit_tick_with_budget(3).unwrap();
+    assert_eq!(carried.len(), 2);
+    assert!(!scheduler.has_pending());
+}
+
+#[test]
+fn scheduler_drains_a_lane_larger_than_the_budget() {
+    let mut scheduler = Scheduler::new();
+    scheduler.begin_tick().unwrap();
+    for raw in 1..=5 {
+        scheduler
+            .enqueue_op_in(
+                Lane::Background,
+                PatchOp::Remove {
+                    node: NodeId::new(raw),
+                },
+            )
+            .unwrap();
+    }
+    let mut flushed = scheduler.commit_tick_with_budget(2).unwrap();
+
+    while scheduler.has_pending() {
+        scheduler.begin_tick().unwrap();
+        let batch = scheduler.commit_tick_with_budget(2).unwrap();
+        assert!(!batch.is_empty());
+        flushed.extend(batch);
+    }
+
+    let removed: Vec<_> = (1..=5)
+        .map(|raw| PatchOp::Remove {
+            node: NodeId::new(raw),
+        })
+        .collect();
+    assert_eq!(flushed, removed);
+}
+
+#[test]
+fn scheduler_flushes_structural_ops_before_ops_that_depend_on_them() {
+    let mut scheduler = Scheduler::new();
+    let created = NodeId::new(5);
+    let removed = NodeId::new(7);
+    scheduler.begin_tick().unwrap();
+    scheduler
+        .enqueue_op_in(
+            Lane::Background,
+            PatchOp::EnsureNode {
+                node: created,
+                tag: "li".to_string(),
+            },
+        )
+        .unwrap();
+    scheduler
+        .enqueue_op(PatchOp::SetText {
+            node: created,
+            text: "new".to_string(),
+        })
+        .unwrap();
+    scheduler
+        .enqueue_op_in(
+            Lane::Background,
+            PatchOp::SetText {
+                node: removed,
+                text: "stale".to_string(),
+            },
+        )
+        .unwrap();
+    scheduler
+        .enqueue_op(PatchOp::Remove { node: removed })
+        .unwrap();
+
+    // Both background ops are over budget but flush with the input ops that
+    // depend on them.
+    let batch = scheduler.commit_tick_with_budget(0).unwrap();
+
+    assert_eq!(
+        batch,
+        vec![
+            PatchOp::EnsureNode {
+                node: created,
+                tag: "li".to_string(),
+            },
+            PatchOp::SetText {
+                node: created,
+                text: "new".to_string(),
+            },
+            PatchOp::Remove { node: removed },
+        ]
+    );
+    assert!(!scheduler.has_pending());
+}
+
+#[test]
+fn scheduler_newer_write_supersedes_carried_lane() {
+    let mut scheduler = Scheduler::new();
+    let node = NodeId::new(1);
+
+    scheduler.begin_tick().unwrap();
+    scheduler
+        .enqueue_op_in(
+            Lane::Background,
+            PatchOp::SetText {
+                node,
+                text: "stale".to_string(),
+            },
+        )
+        .unwrap();
+    scheduler.commit_tick_with_budget(0).unwrap();
+
+    scheduler.begin_tick().unwrap();
+    scheduler
+        .enqueue_op(PatchOp::SetText {
+            node,
+            text: "fresh".to_string(),
+        })
+        .unwrap();
+    let batch = scheduler.commit_tick().unwrap();
+
+    assert_eq!(
+        batch,
+        vec![PatchOp::SetText {
+            node,
+            text: "fresh".to_string(),
+        }]
+    );
+}
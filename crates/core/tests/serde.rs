@@ -0,0 +1,121 @@
+#![cfg(feature = "serde")]
+
+use crust_core::{
+    GuardrailEvent, NodeId, PatchBatch, PatchOp, PhaseDurations, TickResult, TickTelemetry,
+    WorkBreakdown,
+};
+use serde_json::json;
+
+#[test]
+fn patch_ops_serialize_to_host_contract() {
+    let ops = vec![
+        PatchOp::EnsureNode {
+            node: NodeId::new(1),
+            tag: "div".to_string(),
+        },
+        PatchOp::SetText {
+            node: NodeId::new(1),
+            text: "hello".to_string(),
+        },
+        PatchOp::Insert {
+            parent: NodeId::new(1),
+            child: NodeId::new(2),
+        },
+        PatchOp::InsertBefore {
+            parent: NodeId::new(1),
+            child: NodeId::new(3),
+            before: NodeId::new(2),
+        },
+    ];
+
+    let value = serde_json::to_value(&ops).unwrap();
+
+    assert_eq!(
+        value,
+        json!([
+            { "kind": "EnsureNode", "nodeId": 1, "tag": "div" },
+            { "kind": "SetText", "nodeId": 1, "value": "hello" },
+            { "kind": "AppendChild", "parentId": 1, "childId": 2 },
+            { "kind": "InsertBefore", "parentId": 1, "childId": 3, "beforeId": 2 },
+        ])
+    );
+    let back: Vec<PatchOp> = serde_json::from_value(value).unwrap();
+    assert_eq!(back, ops);
+}
+
+#[test]
+fn patch_batch_round_trips_with_camel_case_meta() {
+    let batch = PatchBatch::rollback("forbidden op").with_tick_id(4);
+
+    let value = serde_json::to_value(&batch).unwrap();
+
+    assert_eq!(
+        value,
+        json!({ "metaKind": "rollback", "ops": [], "tickId": 4, "reason": "forbidden op" })
+    );
+    assert_eq!(serde_json::from_value::<PatchBatch>(value).unwrap(), batch);
+}
+
+#[test]
+fn patch_batch_fingerprint_round_trips_as_a_string() {
+    let batch = PatchBatch::commit(Vec::new()).with_fingerprint();
+    let fingerprint = batch.fingerprint.unwrap();
+
+    let value = serde_json::to_value(&batch).unwrap();
+
+    assert_eq!(value["fingerprint"], json!(fingerprint.to_string()));
+    assert_eq!(serde_json::from_value::<PatchBatch>(value).unwrap(), batch);
+
+    let max = PatchBatch {
+        fingerprint: Some(u64::MAX),
+        ..PatchBatch::default()
+    };
+    let text = serde_json::to_string(&max).unwrap();
+    assert!(text.contains(r#""fingerprint":"18446744073709551615""#));
+    assert_eq!(serde_json::from_str::<PatchBatch>(&text).unwrap(), max);
+}
+
+#[test]
+fn tick_telemetry_serializes_as_tick_stats() {
+    let tick = TickTelemetry {
+        tick_id: 7,
+        result: TickResult::Fallback,
+        durations: PhaseDurations {
+            script_ms: 1.0,
+            style_ms: 2.0,
+            layout_ms: 0.0,
+            render_ms: 0.5,
+            total_ms: 3.5,
+        },
+        work: WorkBreakdown {
+            dom_mutations: 3,
+            nodes_touched: 2,
+            patch_bytes: 40,
+            ..WorkBreakdown::default()
+        },
+        fingerprint: Some(u64::MAX),
+        guardrail: Some(GuardrailEvent::new(
+            "layout thrash",
+            Some("layout".to_string()),
+            TickResult::Fallback,
+        )),
+    };
+
+    let value = serde_json::to_value(&tick).unwrap();
+
+    assert_eq!(value["tick_id"], 7);
+    assert_eq!(value["result"], "fallback");
+    assert_eq!(value["durations"]["total_ms"], 3.5);
+    assert_eq!(value["work"]["dom_mutations"], 3);
+    assert_eq!(value["work"]["patch_bytes"], 40);
+    assert_eq!(
+        value["fallback"],
+        json!({ "kind": "fallback", "reason": "layout thrash", "phase": "layout" })
+    );
+    assert_eq!(value["fingerprint"], "18446744073709551615");
+
+    let back: TickTelemetry = serde_json::from_value(value).unwrap();
+    assert_eq!(back.tick_id, 7);
+    assert_eq!(back.fingerprint, Some(u64::MAX));
+    assert_eq!(back.guardrail.unwrap().phase.as_deref(), Some("layout"));
+}
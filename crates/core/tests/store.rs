@@ -1,4 +1,6 @@
-use crust_core::{DependencyGraph, NodeId, Selector, Store};
+use std::cell::Cell;
+
+use crust_core::{DependencyGraph, NodeId, Selector, SelectorError, Store, StoreSnapshot, Value};
 
 #[test]
 fn selector_reads_register_dependencies() {
@@ -66,3 +68,189 @@ fn selector_recompute_keeps_dependency_order() {
     let second = graph.dependents_of(value_node);
     assert_eq!(second, first);
 }
+
+#[test]
+fn selector_drops_edges_it_no_longer_reads() {
+    let mut store = Store::new();
+    let mut graph = DependencyGraph::new();
+
+    let flag = NodeId::new(1);
+    let left = NodeId::new(2);
+    let right = NodeId::new(3);
+    let selector_node = NodeId::new(4);
+    store.set_value(flag, "left");
+    store.set_value(left, "L");
+    store.set_value(right, "R");
+
+    let selector = Selector::new(selector_node, |ctx| {
+        if ctx.read(flag).is_some_and(|value| value == "left") {
+            ctx.read(left).unwrap_or_default()
+        } else {
+            ctx.read(right).unwrap_or_default()
+        }
+    });
+
+    assert_eq!(selector.evaluate(&store, &mut graph), "L");
+    assert_eq!(graph.dependents_of(left), vec![selector_node]);
+
+    store.set_value(flag, "right");
+    assert_eq!(selector.evaluate(&store, &mut graph), "R");
+
+    assert!(graph.dependents_of(left).is_empty());
+    assert_eq!(graph.dependents_of(right), vec![selector_node]);
+    assert_eq!(graph.dependents_of(flag), vec![selector_node]);
+}
+
+#[test]
+fn selector_failure_keeps_only_the_edges_it_read() {
+    let mut store = Store::new();
+    let mut graph = DependencyGraph::new();
+
+    let flag = NodeId::new(1);
+    let left = NodeId::new(2);
+    let right = NodeId::new(3);
+    let selector_node = NodeId::new(4);
+    store.set_value(flag, "left");
+
+    let selector = Selector::new(selector_node, |ctx| {
+        if ctx.read(flag).is_some_and(|value| value == "left") {
+            return Ok(ctx.read(left).unwrap_or_default());
+        }
+        ctx.read(right);
+        Err(SelectorError::failed("right is unsupported"))
+    });
+
+    assert!(selector.try_evaluate(&store, &mut graph).is_ok());
+
+    store.set_value(flag, "right");
+    assert!(selector.try_evaluate(&store, &mut graph).is_err());
+
+    assert!(graph.dependents_of(left).is_empty());
+    assert_eq!(graph.dependents_of(right), vec![selector_node]);
+    assert_eq!(graph.dependents_of(flag), vec![selector_node]);
+}
+
+#[test]
+fn store_versions_only_move_on_change() {
+    let mut store = Store::new();
+    let node = NodeId::new(1);
+    assert_eq!(store.version(node), 0);
+
+    store.set_value(node, "a");
+    let written = store.version(node);
+    store.set_value(node, "a");
+    assert!(written > 0);
+    assert_eq!(store.version(node), written);
+
+    store.set_value(node, "b");
+    let changed = store.version(node);
+    store.remove_value(node);
+    let removed = store.version(node);
+    store.remove_value(node);
+    assert!(written < changed && changed < removed);
+    assert_eq!(store.version(node), removed);
+}
+
+#[test]
+fn selector_reuses_output_while_inputs_are_unchanged() {
+    let mut store = Store::new();
+    let mut graph = DependencyGraph::new();
+    let value_node = NodeId::new(1);
+    let other = NodeId::new(3);
+    store.set_value(value_node, "alpha");
+    let runs = Cell::new(0);
+
+    let selector = Selector::new(NodeId::new(2), |ctx| {
+        runs.set(runs.get() + 1);
+        ctx.read(value_node).unwrap_or_default()
+    });
+
+    selector.evaluate(&store, &mut graph);
+    store.set_value(other, "unrelated");
+    assert_eq!(selector.evaluate(&store, &mut graph), "alpha");
+    assert_eq!(runs.get(), 1);
+
+    store.set_value(value_node, "beta");
+    assert_eq!(selector.evaluate(&store, &mut graph), "beta");
+    assert_eq!(runs.get(), 2);
+
+    selector.invalidate();
+    selector.evaluate(&store, &mut graph);
+    assert_eq!(runs.get(), 3);
+}
+
+#[test]
+fn selector_cache_tells_clones_of_a_store_apart() {
+    let mut graph = DependencyGraph::new();
+    let node = NodeId::new(1);
+    let mut a = Store::new();
+    let mut b = a.clone();
+    a.set_value(node, 1);
+    b.set_value(node, 2);
+
+    let selector = Selector::new(NodeId::new(2), |ctx| ctx.read(node).unwrap_or_default());
+
+    assert_eq!(selector.evaluate(&a, &mut graph), Value::from(1));
+    assert_eq!(selector.evaluate(&b, &mut graph), Value::from(2));
+}
+
+#[test]
+fn store_snapshots_keep_their_values_after_writes() {
+    let mut store = Store::new();
+    let ids: Vec<NodeId> = (0..2000)
+        .map(|n| NodeId::new(n * 37))
+        .chain([NodeId::new(1 << 40), NodeId::new(u64::MAX)])
+        .collect();
+    for (index, &node) in ids.iter().enumerate() {
+        store.set_value(node, index as i64);
+    }
+    let before = store.snapshot();
+
+    for &node in ids.iter().step_by(2) {
+        store.remove_value(node);
+    }
+    store.set_value(ids[1], "changed");
+    let after = store.snapshot();
+    store.set_value(ids[3], "later");
+
+    for (index, &node) in ids.iter().enumerate() {
+        assert_eq!(before.get_value(node), Some(&Value::from(index as i64)));
+    }
+    assert_eq!(after.get_value(ids[0]), None);
+    assert_eq!(after.get_value(ids[1]), Some(&Value::from("changed")));
+    assert_eq!(after.get_value(ids[3]), Some(&Value::from(3)));
+    assert_eq!(store.get_value(ids[3]), Some(&Value::from("later")));
+    assert_eq!(
+        store.get_value(*ids.last().unwrap()),
+        Some(&Value::from(2001))
+    );
+    assert_eq!(store.get_value(NodeId::new(1)), None);
+}
+
+#[test]
+fn store_restore_keeps_versions_unique() {
+    let mut store = Store::new();
+    let node = NodeId::new(1);
+    store.set_value(node, "a");
+    let snapshot = store.snapshot();
+    let original = store.version(node);
+
+    store.set_value(node, "b");
+    let abandoned = store.version(node);
+    store.restore(&snapshot);
+
+    assert_eq!(store.get_value(node), Some(&Value::from("a")));
+    assert_eq!(store.version(node), original);
+    assert_eq!(snapshot.version(node), original);
+
+    store.set_value(node, "c");
+    assert_ne!(store.version(node), abandoned);
+    assert!(store.version(node) > abandoned);
+}
+
+#[test]
+fn stores_and_snapshots_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Store>();
+    assert_send_sync::<StoreSnapshot>();
+}
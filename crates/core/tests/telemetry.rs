@@ -2,7 +2,10 @@
 
 use std::time::Duration;
 
-use crust_core::{Engine, NodeId, TickResult};
+use crust_core::{
+    DependencyGraph, Engine, Lane, NodeId, Selector, SelectorError, Store, TelemetryRecorder,
+    TickBudget, TickResult, Value,
+};
 
 #[test]
 fn telemetry_records_selector_metrics() {
@@ -26,3 +29,95 @@ fn telemetry_records_selector_metrics() {
     assert_eq!(tick.work.elements_invalidated, 3);
     assert_eq!(tick.work.dom_mutations, 1);
 }
+
+#[test]
+fn telemetry_counts_skipped_patches() {
+    let mut engine = Engine::new();
+    let node = NodeId::new(1);
+
+    engine.begin_tick().unwrap();
+    engine.set_value(node, "steady").unwrap();
+    engine.commit().unwrap();
+
+    engine.begin_tick().unwrap();
+    engine.set_value(node, "steady").unwrap();
+    let batch = engine.commit().unwrap();
+
+    assert!(batch.is_empty());
+    let tick = engine.telemetry().last_tick().unwrap();
+    assert_eq!(tick.work.patches_skipped, 1);
+    assert_eq!(tick.work.dom_mutations, 0);
+}
+
+#[test]
+fn telemetry_records_deferred_work() {
+    let mut engine = Engine::new();
+    let input = NodeId::new(1);
+    engine.register_selector_in(Lane::Background, NodeId::new(2), move |ctx| {
+        ctx.read(input).unwrap_or_default()
+    });
+
+    engine.begin_tick().unwrap();
+    engine.set_value(input, "typed").unwrap();
+    engine.commit_with_budget(TickBudget::ops(1)).unwrap();
+
+    let tick = engine.telemetry().last_tick().unwrap();
+    assert_eq!(tick.work.selectors_deferred, 1);
+    assert_eq!(tick.work.ops_deferred, 0);
+    assert_eq!(tick.work.dom_mutations, 1);
+}
+
+#[test]
+fn telemetry_records_rollback_guardrail() {
+    let mut engine = Engine::new();
+    engine.begin_tick().unwrap();
+    engine.set_value(NodeId::new(1), "discarded").unwrap();
+    engine.rollback("forbidden op").unwrap();
+
+    let tick = engine.telemetry().last_tick().unwrap();
+    assert_eq!(tick.result, TickResult::Rollback);
+    let guardrail = tick.guardrail.as_ref().unwrap();
+    assert_eq!(guardrail.kind, TickResult::Rollback);
+    assert_eq!(guardrail.reason, "forbidden op");
+}
+
+#[test]
+fn telemetry_names_failed_selector_and_phase() {
+    let mut engine = Engine::new();
+    engine.register_selector(NodeId::new(7), |_ctx| {
+        Err::<Value, _>(SelectorError::failed("no data"))
+    });
+
+    engine.begin_tick().unwrap();
+    engine.commit().unwrap();
+
+    let tick = engine.telemetry().last_tick().unwrap();
+    assert_eq!(tick.result, TickResult::Fallback);
+    let guardrail = tick.guardrail.as_ref().unwrap();
+    assert_eq!(guardrail.reason, "selector 7 failed: no data");
+    assert_eq!(guardrail.phase.as_deref(), Some("script"));
+}
+
+#[test]
+fn telemetry_counts_selector_cache_hits_and_misses() {
+    let mut recorder = TelemetryRecorder::new();
+    let mut store = Store::new();
+    let mut graph = DependencyGraph::new();
+    let input = NodeId::new(1);
+    store.set_value(input, "a");
+    let selector = Selector::new(NodeId::new(2), move |ctx| {
+        ctx.read(input).unwrap_or_default()
+    });
+
+    recorder.begin_tick();
+    selector.evaluate_with_recorder(&store, &mut graph, Some(&mut recorder));
+    selector.evaluate_with_recorder(&store, &mut graph, Some(&mut recorder));
+    store.set_value(input, "b");
+    selector.evaluate_with_recorder(&store, &mut graph, Some(&mut recorder));
+    recorder.finalize_tick(TickResult::Commit);
+
+    let tick = recorder.last_tick().unwrap();
+    assert_eq!(tick.work.selector_cache_hits, 1);
+    assert_eq!(tick.work.selector_cache_misses, 2);
+    assert_eq!(tick.work.selectors_evaluated, 2);
+}
@@ -0,0 +1,29 @@
+use std::collections::BTreeMap;
+
+use crust_core::Value;
+
+#[test]
+fn scalars_render_bare_text() {
+    assert_eq!(Value::Null.to_text(), "");
+    assert_eq!(Value::from(true).to_text(), "true");
+    assert_eq!(Value::from(42).to_text(), "42");
+    assert_eq!(Value::from(1.5).to_text(), "1.5");
+    assert_eq!(Value::from("plain \"text\"").to_text(), "plain \"text\"");
+}
+
+#[test]
+fn collections_render_as_json() {
+    let mut record = BTreeMap::new();
+    record.insert("name".to_string(), Value::from("ada"));
+    record.insert("tags".to_string(), Value::from(vec!["a", "b"]));
+    record.insert("age".to_string(), Value::from(36));
+
+    assert_eq!(
+        Value::Map(record).to_text(),
+        r#"{"age":36,"name":"ada","tags":["a","b"]}"#
+    );
+    assert_eq!(
+        Value::from(vec![Value::Null, Value::from(false)]).to_text(),
+        "[null,false]"
+    );
+}
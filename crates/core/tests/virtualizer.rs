@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+
+use crust_core::{NodeId, PatchOp, VirtualRow, Virtualizer};
+
+fn rows(count: u64) -> Vec<VirtualRow> {
+    (0..count)
+        .map(|id| {
+            VirtualRow::new(
+                format!("row-{id}"),
+                NodeId::new(100 + id),
+                format!("Row {id}"),
+            )
+        })
+        .collect()
+}
+
+fn list(count: u64) -> Virtualizer {
+    let mut virtualizer = Virtualizer::new(NodeId::new(1), 20.0).with_viewport(0.0, 100.0);
+    virtualizer.set_rows(rows(count));
+    virtualizer
+}
+
+fn mounted_ids(virtualizer: &Virtualizer) -> Vec<u64> {
+    virtualizer
+        .mounted()
+        .iter()
+        .map(|row| row.node.raw() - 100)
+        .collect()
+}
+
+#[test]
+fn initial_update_mounts_only_the_window() {
+    let mut virtualizer = list(10_000).with_overscan(1);
+
+    let ops = virtualizer.update();
+
+    assert_eq!(virtualizer.visible_range(), 0..6);
+    assert_eq!(mounted_ids(&virtualizer), vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(ops.len(), 18);
+    assert_eq!(
+        ops[..2],
+        [
+            PatchOp::EnsureNode {
+                node: NodeId::new(100),
+                tag: "div".into(),
+            },
+            PatchOp::SetText {
+                node: NodeId::new(100),
+                text: "Row 0".into(),
+            },
+        ]
+    );
+    assert_eq!(
+        ops[12..14],
+        [
+            PatchOp::Insert {
+                parent: NodeId::new(1),
+                child: NodeId::new(105),
+            },
+            PatchOp::InsertBefore {
+                parent: NodeId::new(1),
+                child: NodeId::new(104),
+                before: NodeId::new(105),
+            },
+        ]
+    );
+    assert_eq!(virtualizer.total_size(), 200_000.0);
+}
+
+#[test]
+fn scrolling_patches_only_rows_entering_or_leaving() {
+    let mut virtualizer = list(50);
+    virtualizer.update();
+
+    virtualizer.scroll_to(40.0);
+    let ops = virtualizer.update();
+
+    assert_eq!(mounted_ids(&virtualizer), vec![2, 3, 4, 5, 6]);
+    assert_eq!(
+        ops,
+        vec![
+            PatchOp::Remove {
+                node: NodeId::new(100),
+            },
+            PatchOp::Remove {
+                node: NodeId::new(101),
+            },
+            PatchOp::EnsureNode {
+                node: NodeId::new(105),
+                tag: "div".into(),
+            },
+            PatchOp::SetText {
+                node: NodeId::new(105),
+                text: "Row 5".into(),
+            },
+            PatchOp::EnsureNode {
+                node: NodeId::new(106),
+                tag: "div".into(),
+            },
+            PatchOp::SetText {
+                node: NodeId::new(106),
+                text: "Row 6".into(),
+            },
+            PatchOp::Insert {
+                parent: NodeId::new(1),
+                child: NodeId::new(106),
+            },
+            PatchOp::InsertBefore {
+                parent: NodeId::new(1),
+                child: NodeId::new(105),
+                before: NodeId::new(106),
+            },
+        ]
+    );
+    assert!(virtualizer.update().is_empty());
+}
+
+#[test]
+fn measured_sizes_override_the_estimate() {
+    let mut virtualizer = list(50);
+    virtualizer.measure("row-0", 90.0);
+
+    virtualizer.update();
+
+    assert_eq!(mounted_ids(&virtualizer), vec![0, 1]);
+    assert_eq!(virtualizer.offset_of(2), 110.0);
+}
+
+#[test]
+fn changed_text_of_mounted_row_emits_set_text() {
+    let mut virtualizer = list(5);
+    virtualizer.update();
+
+    let mut next = rows(5);
+    next[1].text = "Renamed".into();
+    virtualizer.set_rows(next);
+
+    assert_eq!(
+        virtualizer.update(),
+        vec![PatchOp::SetText {
+            node: NodeId::new(101),
+            text: "Renamed".into(),
+        }]
+    );
+}
+
+/// Applies `ops` the way the host does, panicking on ops that reference a
+/// node that does not exist. `Remove` destroys the node and its children.
+fn apply(live: &mut BTreeSet<u64>, children: &mut Vec<u64>, ops: &[PatchOp]) {
+    let check = |live: &BTreeSet<u64>, node: NodeId| {
+        assert!(live.contains(&node.raw()), "Unknown node {}", node.raw());
+    };
+    for op in ops {
+        match op {
+            PatchOp::EnsureNode { node, .. } => {
+                live.insert(node.raw());
+            }
+            PatchOp::SetText { node, .. } => check(live, *node),
+            PatchOp::Insert { parent, child } => {
+                check(live, *parent);
+                check(live, *child);
+                children.retain(|&id| id != child.raw());
+                children.push(child.raw());
+            }
+            PatchOp::InsertBefore {
+                parent,
+                child,
+                before,
+            } => {
+                check(live, *parent);
+                check(live, *child);
+                check(live, *before);
+                children.retain(|&id| id != child.raw());
+                let at = children.iter().position(|&id| id == before.raw()).unwrap();
+                children.insert(at, child.raw());
+            }
+            PatchOp::Remove { node } => {
+                check(live, *node);
+                live.remove(&node.raw());
+                children.retain(|&id| id != node.raw());
+            }
+            other => panic!("unexpected op {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn scrolling_back_recreates_rows_the_host_destroyed() {
+    let mut virtualizer = list(10_000);
+    let mut live = BTreeSet::from([1]);
+    let mut children = Vec::new();
+
+    for offset in [0.0, 40.0, 500.0, 20.0, 0.0] {
+        virtualizer.scroll_to(offset);
+        let ops = virtualizer.update();
+        apply(&mut live, &mut children, &ops);
+
+        let mounted: Vec<u64> = virtualizer
+            .mounted()
+            .iter()
+            .map(|row| row.node.raw())
+            .collect();
+        assert_eq!(children, mounted);
+        assert_eq!(live.len(), mounted.len() + 1);
+    }
+    assert_eq!(mounted_ids(&virtualizer), vec![0, 1, 2, 3, 4]);
+}
@@ -0,0 +1,111 @@
+use crust_core::{
+    decode_batch, encode_batch, NodeId, PatchBatch, PatchOp, WireError, WIRE_VERSION,
+};
+
+fn sample_batch() -> PatchBatch {
+    let list = NodeId::new(1);
+    let ops = vec![
+        PatchOp::EnsureNode {
+            node: list,
+            tag: "ul".to_string(),
+        },
+        PatchOp::EnsureNode {
+            node: NodeId::new(300),
+            tag: "li".to_string(),
+        },
+        PatchOp::EnsureNode {
+            node: NodeId::new(301),
+            tag: "li".to_string(),
+        },
+        PatchOp::Insert {
+            parent: list,
+            child: NodeId::new(300),
+        },
+        PatchOp::InsertBefore {
+            parent: list,
+            child: NodeId::new(301),
+            before: NodeId::new(300),
+        },
+        PatchOp::SetText {
+            node: NodeId::new(300),
+            text: "héllo".to_string(),
+        },
+        PatchOp::SetAttr {
+            node: NodeId::new(301),
+            name: "class".to_string(),
+            value: "row".to_string(),
+        },
+        PatchOp::RemoveAttr {
+            node: NodeId::new(300),
+            name: "class".to_string(),
+        },
+        PatchOp::Remove {
+            node: NodeId::new(u64::MAX),
+        },
+    ];
+    PatchBatch::commit(ops)
+        .with_batch_id(7)
+        .with_tick_id(42)
+        .with_fingerprint()
+}
+
+#[test]
+fn wire_round_trips_every_op_and_metadata() {
+    let batch = sample_batch();
+
+    let bytes = encode_batch(&batch);
+
+    assert_eq!(&bytes[..4], b"JCPB");
+    assert_eq!(bytes[4], WIRE_VERSION);
+    assert_eq!(decode_batch(&bytes), Ok(batch));
+}
+
+#[test]
+fn wire_round_trips_rollback_reason() {
+    let batch = PatchBatch::rollback("forbidden op").with_tick_id(3);
+
+    assert_eq!(decode_batch(&encode_batch(&batch)), Ok(batch));
+}
+
+#[test]
+fn wire_interns_repeated_strings() {
+    let make = |count: u64| {
+        let ops = (0..count)
+            .map(|raw| PatchOp::SetAttr {
+                node: NodeId::new(raw),
+                name: "data-row-state".to_string(),
+                value: "selected-and-highlighted".to_string(),
+            })
+            .collect();
+        encode_batch(&PatchBatch::commit(ops))
+    };
+
+    let one = make(1).len();
+    let many = make(11).len();
+
+    // Ten more ops only add an opcode, a node id and two one-byte indices each.
+    assert_eq!(many - one, 10 * 4);
+}
+
+#[test]
+fn wire_rejects_malformed_buffers() {
+    let bytes = encode_batch(&sample_batch());
+
+    assert_eq!(decode_batch(b"NOPE\x01"), Err(WireError::BadMagic));
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[4] = WIRE_VERSION + 1;
+    assert_eq!(
+        decode_batch(&wrong_version),
+        Err(WireError::UnsupportedVersion(WIRE_VERSION + 1))
+    );
+
+    assert_eq!(
+        decode_batch(&bytes[..bytes.len() - 1]),
+        Err(WireError::UnexpectedEof)
+    );
+
+    let mut trailing = bytes;
+    trailing.push(0);
+    assert_eq!(decode_batch(&trailing), Err(WireError::TrailingBytes));
+}
@@ -13,7 +13,7 @@ impl EffectLog {
 
     pub fn commit(&mut self) -> usize {
         let count = self.pending.len();
-        self.committed.extend(self.pending.drain(..));
+        self.committed.append(&mut self.pending);
         count
     }
 
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Builds the bindings for wasm32 and runs the node tests against them.
+# Requires the wasm32-unknown-unknown target and a wasm-bindgen CLI matching
+# the wasm-bindgen version in Cargo.lock.
+set -e
+
+crate_dir="$(cd "$(dirname "$0")/.." && pwd)"
+target_dir="$(cd "$crate_dir/../.." && pwd)/target"
+
+cargo build --locked -p crust_wasm --target wasm32-unknown-unknown --release
+wasm-bindgen --target nodejs --out-dir "$crate_dir/pkg" \
+  "$target_dir/wasm32-unknown-unknown/release/crust_wasm.wasm"
+node --test "$crate_dir/tests/node/"
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use crust_core::{NodeId, PatchBatch, PatchOp, SchedulerError, Value};
+use js_sys::{Array, Object, JSON};
+use wasm_bindgen::prelude::*;
+
+/// `crust_core::Engine` exported to JavaScript.
+///
+/// Every tick-ending call returns one batch object shaped like the
+/// `packages/js-host` `commitBatch` input, so the boundary is crossed once per
+/// tick. The shape comes from the `crust_core` serde impls.
+#[wasm_bindgen(js_name = Engine)]
+#[derive(Debug, Default)]
+pub struct WasmEngine {
+    inner: crust_core::Engine,
+}
+
+#[wasm_bindgen(js_class = Engine)]
+impl WasmEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[wasm_bindgen(js_name = beginTick)]
+    pub fn begin_tick(&mut self) -> Result<(), JsError> {
+        self.inner.begin_tick().map_err(scheduler_error)
+    }
+
+    #[wasm_bindgen(js_name = setValue)]
+    pub fn set_value(&mut self, node_id: f64, value: JsValue) -> Result<(), JsError> {
+        let node = node_from_js(node_id)?;
+        let value = value_from_js(&value)?;
+        self.inner.set_value(node, value).map_err(scheduler_error)
+    }
+
+    #[wasm_bindgen(js_name = ensureNode)]
+    pub fn ensure_node(&mut self, node_id: f64, tag: String) -> Result<(), JsError> {
+        let node = node_from_js(node_id)?;
+        self.enqueue(PatchOp::EnsureNode { node, tag })
+    }
+
+    #[wasm_bindgen(js_name = setAttr)]
+    pub fn set_attr(&mut self, node_id: f64, name: String, value: String) -> Result<(), JsError> {
+        let node = node_from_js(node_id)?;
+        self.enqueue(PatchOp::SetAttr { node, name, value })
+    }
+
+    #[wasm_bindgen(js_name = appendChild)]
+    pub fn append_child(&mut self, parent_id: f64, child_id: f64) -> Result<(), JsError> {
+        let parent = node_from_js(parent_id)?;
+        let child = node_from_js(child_id)?;
+        self.enqueue(PatchOp::Insert { parent, child })
+    }
+
+    #[wasm_bindgen(js_name = removeNode)]
+    pub fn remove_node(&mut self, node_id: f64) -> Result<(), JsError> {
+        let node = node_from_js(node_id)?;
+        self.enqueue(PatchOp::Remove { node })
+    }
+
+    pub fn commit(&mut self) -> Result<JsValue, JsError> {
+        let batch = self.inner.commit().map_err(scheduler_error)?;
+        batch_to_js(&batch)
+    }
+
+    pub fn rollback(&mut self, reason: String) -> Result<JsValue, JsError> {
+        let batch = self.inner.rollback(reason).map_err(scheduler_error)?;
+        batch_to_js(&batch)
+    }
+
+    pub fn fallback(&mut self, reason: String) -> Result<JsValue, JsError> {
+        let batch = self.inner.fallback(reason).map_err(scheduler_error)?;
+        batch_to_js(&batch)
+    }
+
+    fn enqueue(&mut self, op: PatchOp) -> Result<(), JsError> {
+        self.inner.enqueue_op(op).map_err(scheduler_error)
+    }
+}
+
+fn batch_to_js(batch: &PatchBatch) -> Result<JsValue, JsError> {
+    let json = serde_json::to_string(batch).map_err(|err| JsError::new(&err.to_string()))?;
+    JSON::parse(&json).map_err(|_| JsError::new("invalid batch json"))
+}
+
+fn scheduler_error(err: SchedulerError) -> JsError {
+    let message = match err {
+        SchedulerError::TickAlreadyStarted => "Tick already started",
+        SchedulerError::TickNotStarted => "Tick not started",
+    };
+    JsError::new(message)
+}
+
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+fn node_from_js(raw: f64) -> Result<NodeId, JsError> {
+    if raw.fract() != 0.0 || !(0.0..=MAX_SAFE_INTEGER).contains(&raw) {
+        return Err(JsError::new("node ids must be non-negative safe integers"));
+    }
+    Ok(NodeId::new(raw as u64))
+}
+
+fn value_from_js(value: &JsValue) -> Result<Value, JsError> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(Value::Null);
+    }
+    if let Some(flag) = value.as_bool() {
+        return Ok(Value::Bool(flag));
+    }
+    if let Some(number) = value.as_f64() {
+        if number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER {
+            return Ok(Value::Int(number as i64));
+        }
+        return Ok(Value::Float(number));
+    }
+    if let Some(text) = value.as_string() {
+        return Ok(Value::Text(text));
+    }
+    if Array::is_array(value) {
+        let items = Array::from(value)
+            .iter()
+            .map(|item| value_from_js(&item))
+            .collect::<Result<_, _>>()?;
+        return Ok(Value::List(items));
+    }
+    if value.is_object() {
+        let mut entries = BTreeMap::new();
+        for entry in Object::entries(value.unchecked_ref()).iter() {
+            let pair = Array::from(&entry);
+            let key = pair.get(0).as_string().unwrap_or_default();
+            entries.insert(key, value_from_js(&pair.get(1))?);
+        }
+        return Ok(Value::Map(entries));
+    }
+    Err(JsError::new("unsupported value type"))
+}
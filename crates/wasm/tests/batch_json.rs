@@ -0,0 +1,72 @@
+use crust_core::{Engine, NodeId, PatchBatch, PatchOp};
+
+#[test]
+fn commit_batch_matches_js_host_shape() {
+    let mut engine = Engine::new();
+    engine.begin_tick().unwrap();
+    engine
+        .enqueue_op(PatchOp::EnsureNode {
+            node: NodeId::new(1),
+            tag: "div".to_string(),
+        })
+        .unwrap();
+    engine.set_value(NodeId::new(1), "say \"hi\"").unwrap();
+    let batch = PatchBatch {
+        fingerprint: None,
+        ..engine.commit().unwrap()
+    };
+
+    assert_eq!(
+        serde_json::to_string(&batch).unwrap(),
+        concat!(
+            r#"{"metaKind":"commit","ops":["#,
+            r#"{"kind":"EnsureNode","nodeId":1,"tag":"div"},"#,
+            r#"{"kind":"SetText","nodeId":1,"value":"say \"hi\""}"#,
+            r#"],"batchId":1,"tickId":1}"#
+        )
+    );
+}
+
+#[test]
+fn structural_ops_use_host_field_names() {
+    let batch = PatchBatch::commit(vec![
+        PatchOp::Insert {
+            parent: NodeId::new(1),
+            child: NodeId::new(2),
+        },
+        PatchOp::InsertBefore {
+            parent: NodeId::new(1),
+            child: NodeId::new(3),
+            before: NodeId::new(2),
+        },
+        PatchOp::RemoveAttr {
+            node: NodeId::new(2),
+            name: "hidden".to_string(),
+        },
+        PatchOp::Remove {
+            node: NodeId::new(3),
+        },
+    ]);
+
+    assert_eq!(
+        serde_json::to_string(&batch).unwrap(),
+        concat!(
+            r#"{"metaKind":"commit","ops":["#,
+            r#"{"kind":"AppendChild","parentId":1,"childId":2},"#,
+            r#"{"kind":"InsertBefore","parentId":1,"childId":3,"beforeId":2},"#,
+            r#"{"kind":"RemoveAttr","nodeId":2,"name":"hidden"},"#,
+            r#"{"kind":"Remove","nodeId":3}"#,
+            r#"]}"#
+        )
+    );
+}
+
+#[test]
+fn rollback_batch_carries_reason_and_no_ops() {
+    let batch = PatchBatch::rollback("forbidden op").with_fingerprint();
+
+    let json = serde_json::to_string(&batch).unwrap();
+
+    assert!(json
+        .starts_with(r#"{"metaKind":"rollback","ops":[],"reason":"forbidden op","fingerprint":""#));
+}
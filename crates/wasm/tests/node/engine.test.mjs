@@ -0,0 +1,47 @@
+import assert from 'node:assert/strict';
+import { createRequire } from 'node:module';
+import test from 'node:test';
+import { createRunner } from '../../../../packages/js-host/src/runner.js';
+
+const require = createRequire(import.meta.url);
+const { Engine } = require('../../pkg/crust_wasm.js');
+
+test('wasm engine batches apply through the js host', () => {
+  const engine = new Engine();
+  const runner = createRunner();
+
+  engine.beginTick();
+  engine.ensureNode(1, 'div');
+  engine.ensureNode(2, 'span');
+  engine.appendChild(1, 2);
+  engine.setValue(2, 'hello');
+  const batch = engine.commit();
+
+  assert.equal(batch.metaKind, 'commit');
+  assert.equal(batch.tickId, 1);
+  assert.deepEqual(batch.ops[3], { kind: 'SetText', nodeId: 2, value: 'hello' });
+
+  runner.beginTick();
+  const serialized = JSON.parse(runner.commitBatch(batch));
+  assert.deepEqual(serialized[0].children, [2]);
+  assert.equal(serialized[1].text, 'hello');
+});
+
+test('typed values render as text and rollback batches carry no ops', () => {
+  const engine = new Engine();
+
+  engine.beginTick();
+  engine.ensureNode(1, 'pre');
+  engine.setValue(1, { count: 3, tags: ['a', 'b'] });
+  const batch = engine.commit();
+  assert.equal(batch.ops[1].value, '{"count":3,"tags":["a","b"]}');
+
+  engine.beginTick();
+  engine.setValue(1, 4.5);
+  const rollback = engine.rollback('forbidden op');
+  assert.deepEqual(rollback.ops, []);
+  assert.equal(rollback.metaKind, 'rollback');
+  assert.equal(rollback.reason, 'forbidden op');
+
+  assert.throws(() => engine.commit(), /Tick not started/);
+});
@@ -23,10 +23,18 @@ export function applyPatchBatch(dom, batch) {
         dom.setAttr(op.nodeId, op.name, op.value);
         break;
       }
+      case 'RemoveAttr': {
+        dom.removeAttr(op.nodeId, op.name);
+        break;
+      }
       case 'AppendChild': {
         dom.appendChild(op.parentId, op.childId);
         break;
       }
+      case 'InsertBefore': {
+        dom.insertBefore(op.parentId, op.childId, op.beforeId);
+        break;
+      }
       case 'Remove': {
         dom.removeNode(op.nodeId);
         break;
@@ -85,7 +85,17 @@ export class DomModel {
     node.attrs.set(name, value);
   }
 
+  removeAttr(id, name) {
+    this.assertMutationAllowed();
+    const node = this.getNode(id);
+    node.attrs.delete(name);
+  }
+
   appendChild(parentId, childId) {
+    this.insertBefore(parentId, childId, null);
+  }
+
+  insertBefore(parentId, childId, beforeId) {
     this.assertMutationAllowed();
     const parent = this.getNode(parentId);
     const child = this.getNode(childId);
@@ -99,7 +109,13 @@ export class DomModel {
     }
 
     child.parent = parent;
-    parent.children.push(childId);
+    // A missing or detached reference node falls back to appending.
+    const beforeIdx = beforeId === null ? -1 : parent.children.indexOf(beforeId);
+    if (beforeIdx >= 0) {
+      parent.children.splice(beforeIdx, 0, childId);
+    } else {
+      parent.children.push(childId);
+    }
   }
 
   removeNode(id) {
@@ -59,3 +59,24 @@ test('ensureNode is idempotent and serializer is stable across runs', () => {
 
   assert.equal(firstSerialized, secondSerialized);
 });
+
+test('insertBefore positions children and removeAttr clears attributes', () => {
+  const batch = {
+    ops: [
+      { kind: 'EnsureNode', nodeId: 1, tag: 'ul' },
+      { kind: 'EnsureNode', nodeId: 2, tag: 'li' },
+      { kind: 'EnsureNode', nodeId: 3, tag: 'li' },
+      { kind: 'AppendChild', parentId: 1, childId: 2 },
+      { kind: 'InsertBefore', parentId: 1, childId: 3, beforeId: 2 },
+      { kind: 'SetAttr', nodeId: 2, name: 'class', value: 'old' },
+      { kind: 'RemoveAttr', nodeId: 2, name: 'class' },
+    ],
+  };
+
+  const dom = createDomModel();
+  dom.runMutating(() => applyPatchBatch(dom, batch));
+
+  const [list, second] = JSON.parse(dom.serialize());
+  assert.deepEqual(list.children, [3, 2]);
+  assert.deepEqual(second.attrs, []);
+});